@@ -0,0 +1,12 @@
+-- accounts table - stores account status from account events
+CREATE TABLE IF NOT EXISTS accounts (
+    did TEXT PRIMARY KEY,
+    status TEXT NOT NULL, -- active, deactivated, suspended, takendown, deleted
+
+    -- metadata
+    seq BIGINT NOT NULL,
+    status_changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_accounts_status ON accounts(status) WHERE status <> 'active';
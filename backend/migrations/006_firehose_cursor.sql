@@ -0,0 +1,6 @@
+-- firehose cursor tracking (relay sequence number)
+CREATE TABLE IF NOT EXISTS firehose_cursor (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    cursor_value BIGINT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,19 @@
+-- backfill_jobs table - durable per-DID queue for full backfills
+CREATE TABLE IF NOT EXISTS backfill_jobs (
+    did TEXT PRIMARY KEY,
+    state TEXT NOT NULL DEFAULT 'pending', -- pending, running, done, failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    repo_rev TEXT,
+
+    -- scheduling
+    run_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+
+    -- metadata
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_backfill_jobs_claim ON backfill_jobs(state, run_after);
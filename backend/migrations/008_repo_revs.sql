@@ -0,0 +1,6 @@
+-- repo_revs table - last repo rev we synced for each DID, used as `since`
+CREATE TABLE IF NOT EXISTS repo_revs (
+    did TEXT PRIMARY KEY,
+    rev TEXT NOT NULL,
+    synced_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
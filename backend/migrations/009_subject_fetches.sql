@@ -0,0 +1,15 @@
+-- subject_fetches table - rsvp subjects (events) we haven't indexed and are
+-- fetching from the event author's PDS
+CREATE TABLE IF NOT EXISTS subject_fetches (
+    uri TEXT PRIMARY KEY,
+    cid TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    run_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    -- metadata
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_subject_fetches_run_after ON subject_fetches(run_after);
@@ -0,0 +1,30 @@
+-- event_revisions table - every version (cid) of every event we've indexed,
+-- so rsvps made against an older version can show what changed since
+CREATE TABLE IF NOT EXISTS event_revisions (
+    uri TEXT NOT NULL,
+    cid TEXT NOT NULL,
+    did TEXT NOT NULL,
+
+    -- event fields as of this revision
+    name TEXT NOT NULL,
+    description TEXT,
+    starts_at TIMESTAMPTZ,
+    ends_at TIMESTAMPTZ,
+    mode TEXT,
+    status TEXT,
+    locations JSONB,
+    uris JSONB,
+
+    -- metadata
+    indexed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    PRIMARY KEY (uri, cid)
+);
+
+CREATE INDEX IF NOT EXISTS idx_event_revisions_did ON event_revisions(did);
+
+-- seed with the versions we already have
+INSERT INTO event_revisions (uri, cid, did, name, description, starts_at, ends_at, mode, status, locations, uris, indexed_at)
+SELECT uri, cid, did, name, description, starts_at, ends_at, mode, status, locations, uris, indexed_at
+FROM events
+ON CONFLICT (uri, cid) DO NOTHING;
@@ -0,0 +1,18 @@
+-- the repo rev each row was written at, so an older version of a record
+-- (e.g. from a backfill CAR) never overwrites a newer one from live ingestion.
+-- rows indexed before this migration have no rev and accept any write
+ALTER TABLE events ADD COLUMN IF NOT EXISTS rev TEXT;
+ALTER TABLE rsvps ADD COLUMN IF NOT EXISTS rev TEXT;
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS rev TEXT;
+
+-- record_tombstones table - deletes we've applied, so an older write of the
+-- same record arriving afterwards doesn't bring it back
+CREATE TABLE IF NOT EXISTS record_tombstones (
+    uri TEXT PRIMARY KEY,
+    did TEXT NOT NULL,
+    rev TEXT NOT NULL,
+    deleted_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_record_tombstones_did ON record_tombstones(did);
+CREATE INDEX IF NOT EXISTS idx_record_tombstones_deleted_at ON record_tombstones(deleted_at);
@@ -0,0 +1,23 @@
+-- failed_records table - records the sink couldn't parse or write, kept with
+-- their raw body so they can be replayed after a lexicon or code fix
+CREATE TABLE IF NOT EXISTS failed_records (
+    uri TEXT PRIMARY KEY,
+    did TEXT NOT NULL,
+    collection TEXT NOT NULL,
+    rkey TEXT NOT NULL,
+    cid TEXT,
+    rev TEXT,
+
+    -- 'json' or 'cbor', depending on where the record came from; both are
+    -- null for a failed delete
+    encoding TEXT,
+    record BYTEA,
+
+    error TEXT NOT NULL,
+    failures INTEGER NOT NULL DEFAULT 1,
+    first_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_failed_records_did ON failed_records(did);
+CREATE INDEX IF NOT EXISTS idx_failed_records_collection ON failed_records(collection);
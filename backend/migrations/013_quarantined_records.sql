@@ -0,0 +1,16 @@
+-- quarantined_records table - records that parsed but failed validation, kept
+-- out of the public tables along with the reasons they were rejected
+CREATE TABLE IF NOT EXISTS quarantined_records (
+    uri TEXT PRIMARY KEY,
+    did TEXT NOT NULL,
+    collection TEXT NOT NULL,
+    rkey TEXT NOT NULL,
+    cid TEXT NOT NULL,
+    rev TEXT,
+    record JSONB NOT NULL,
+    reasons TEXT[] NOT NULL,
+    quarantined_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_quarantined_records_did ON quarantined_records(did);
+CREATE INDEX IF NOT EXISTS idx_quarantined_records_collection ON quarantined_records(collection);
@@ -0,0 +1,51 @@
+-- the original record json, returned verbatim in views and used to rebuild
+-- the denormalized columns with `aktivi-cli reindex`
+ALTER TABLE events ADD COLUMN IF NOT EXISTS record JSONB;
+ALTER TABLE rsvps ADD COLUMN IF NOT EXISTS record JSONB;
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS record JSONB;
+-- events and rsvps already keep their cid; profiles need it to be reindexed
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS cid TEXT;
+ALTER TABLE event_revisions ADD COLUMN IF NOT EXISTS record JSONB;
+
+-- rows indexed before this migration get a record rebuilt from their columns;
+-- the next full sync of each repo replaces it with the original
+UPDATE events
+SET record = jsonb_strip_nulls(jsonb_build_object(
+    '$type', 'community.lexicon.calendar.event',
+    'name', name,
+    'description', description,
+    'createdAt', created_at,
+    'startsAt', starts_at,
+    'endsAt', ends_at,
+    'mode', mode,
+    'status', status,
+    'locations', locations,
+    'uris', uris
+))
+WHERE record IS NULL;
+
+UPDATE rsvps
+SET record = jsonb_build_object(
+    '$type', 'community.lexicon.calendar.rsvp',
+    'subject', jsonb_build_object('uri', subject_uri, 'cid', subject_cid),
+    'status', status
+)
+WHERE record IS NULL;
+
+-- avatar and banner blob refs weren't kept, so they can't be rebuilt
+UPDATE profiles
+SET record = jsonb_strip_nulls(jsonb_build_object(
+    '$type', 'co.aktivi.actor.profile',
+    'displayName', display_name,
+    'description', description
+))
+WHERE record IS NULL;
+
+UPDATE event_revisions er
+SET record = e.record
+FROM events e
+WHERE er.uri = e.uri AND er.cid = e.cid AND er.record IS NULL;
+
+ALTER TABLE events ALTER COLUMN record SET NOT NULL;
+ALTER TABLE rsvps ALTER COLUMN record SET NOT NULL;
+ALTER TABLE profiles ALTER COLUMN record SET NOT NULL;
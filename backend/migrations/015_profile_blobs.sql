@@ -0,0 +1,17 @@
+-- profile avatar and banner blobs - the cid and mime type from the record's
+-- blob ref, replacing the placeholder text columns
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS avatar_cid TEXT;
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS avatar_mime TEXT;
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS banner_cid TEXT;
+ALTER TABLE profiles ADD COLUMN IF NOT EXISTS banner_mime TEXT;
+
+-- blob refs are `{"ref": {"$link": cid}, "mimeType": ...}`, or
+-- `{"cid": ..., "mimeType": ...}` in the legacy format
+UPDATE profiles
+SET avatar_cid = COALESCE(record->'avatar'->'ref'->>'$link', record->'avatar'->>'cid'),
+    avatar_mime = record->'avatar'->>'mimeType',
+    banner_cid = COALESCE(record->'banner'->'ref'->>'$link', record->'banner'->>'cid'),
+    banner_mime = record->'banner'->>'mimeType';
+
+ALTER TABLE profiles DROP COLUMN IF EXISTS avatar;
+ALTER TABLE profiles DROP COLUMN IF EXISTS banner;
@@ -0,0 +1,24 @@
+-- bsky_profiles table - local copies of the Bluesky profiles of event hosts
+-- and rsvp authors, shown for accounts without an aktivi profile
+CREATE TABLE IF NOT EXISTS bsky_profiles (
+    did TEXT PRIMARY KEY,
+    -- where the profile came from: 'appview' for app.bsky.actor.getProfiles
+    source TEXT NOT NULL,
+
+    -- handle the AppView reported, once checked to resolve back to the did
+    handle TEXT,
+    display_name TEXT,
+    description TEXT,
+    avatar_cid TEXT,
+    banner_cid TEXT,
+
+    -- when the profile was last fetched, and when it should be fetched again
+    refreshed_at TIMESTAMPTZ,
+    refresh_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    -- metadata
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_bsky_profiles_refresh_after ON bsky_profiles(refresh_after);
@@ -0,0 +1,5 @@
+-- app.bsky.actor.profile records indexed from live ingestion and backfill.
+-- rows written from a record have source 'record' and the commit they came
+-- from, and AppView refreshes only update their handle
+ALTER TABLE bsky_profiles ADD COLUMN IF NOT EXISTS cid TEXT;
+ALTER TABLE bsky_profiles ADD COLUMN IF NOT EXISTS rev TEXT;
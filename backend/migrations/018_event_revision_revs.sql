@@ -0,0 +1,17 @@
+-- event revisions are keyed and ordered by the repo rev they were written at,
+-- so an event edited back to an earlier version (A -> B -> A) keeps all three
+-- revisions instead of folding the last one into the first
+ALTER TABLE event_revisions ADD COLUMN IF NOT EXISTS rev TEXT;
+
+-- the current version of each event knows its rev. older revisions indexed
+-- before this migration don't, and are listed first, ordered by cid
+UPDATE event_revisions er
+SET rev = e.rev
+FROM events e
+WHERE er.uri = e.uri AND er.cid = e.cid AND er.rev IS NULL;
+
+-- a unique index rather than a primary key, so the revisions without a rev
+-- don't collide with each other
+ALTER TABLE event_revisions DROP CONSTRAINT IF EXISTS event_revisions_pkey;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_event_revisions_uri_rev ON event_revisions(uri, rev);
+CREATE INDEX IF NOT EXISTS idx_event_revisions_uri_cid ON event_revisions(uri, cid);
@@ -0,0 +1,26 @@
+-- profiles indexed before blob refs were kept lost their avatar and banner:
+-- the records rebuilt in 014 have no blob fields, so 015 had nothing to read
+-- them from. their repos are queued for a full sync to index the original
+-- records again; profiles that really have neither are synced once for nothing
+
+-- forgetting the row's rev lets the same version of the record be written again
+UPDATE profiles
+SET rev = NULL
+WHERE avatar_cid IS NULL AND banner_cid IS NULL;
+
+-- and forgetting the synced rev makes the next sync fetch the whole repo
+DELETE FROM repo_revs r
+USING profiles p
+WHERE r.did = p.did AND p.avatar_cid IS NULL AND p.banner_cid IS NULL;
+
+INSERT INTO backfill_jobs (did)
+SELECT did
+FROM profiles
+WHERE avatar_cid IS NULL AND banner_cid IS NULL
+ON CONFLICT (did) DO UPDATE SET
+    state = 'pending',
+    attempts = 0,
+    last_error = NULL,
+    run_after = NOW(),
+    updated_at = NOW()
+WHERE backfill_jobs.state <> 'running';
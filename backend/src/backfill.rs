@@ -1,16 +1,47 @@
 use anyhow::{Context, Result};
-use jacquard_common::types::value;
-use lex_rs::co_aktivi::actor::profile::Profile;
-use lex_rs::community_lexicon::calendar::{event::Event, rsvp::Rsvp};
+use futures::TryStreamExt;
 use repo_stream::{DiskBuilder, Driver, DriverBuilder};
 use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
-use std::io::Cursor;
+use sqlx::{PgConnection, PgPool};
+use std::{
+    collections::HashSet,
+    io,
+    path::Path,
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, BufReader},
+};
+use tokio_util::io::StreamReader;
 use tracing::{info, warn};
 
-const EVENT_COLLECTION: &str = "community.lexicon.calendar.event";
-const RSVP_COLLECTION: &str = "community.lexicon.calendar.rsvp";
-const PROFILE_COLLECTION: &str = "co.aktivi.actor.profile";
+use crate::{
+    mst,
+    sink::{
+        self, Applied, RawRecord, RecordKind, RecordOp, RecordSink, BSKY_PROFILE_COLLECTION,
+        PROFILE_COLLECTION, WANTED_COLLECTIONS,
+    },
+    verify::{CarVerifier, SigningKey, VerifyingReader},
+};
+
+/// Repos larger than this are rejected rather than indexed
+pub const MAX_REPO_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// `since` diffs larger than this are dropped for a full sync, which streams
+/// the repo instead of holding its blocks in memory
+pub const MAX_DIFF_BYTES: u64 = 64 * 1024 * 1024;
+
+/// How long a single repo download may take, including reading the body
+pub const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()
+        .expect("failed to build http client")
+});
 
 #[derive(Debug, Deserialize, Serialize)]
 struct RepoInfo {
@@ -55,16 +86,34 @@ pub async fn fetch_all_dids(collection: &str) -> Result<Vec<String>> {
     Ok(all_dids)
 }
 
-/// Resolve DID to find the user's PDS endpoint
-pub async fn resolve_pds(did: &str) -> Result<String> {
-    let plc_url = format!("https://plc.directory/{}", did);
-    let response = reqwest::get(&plc_url).await?;
-    let doc: serde_json::Value = response.json().await?;
+/// Fetch the DID document for a did:plc or did:web
+pub async fn resolve_did_doc(did: &str) -> Result<serde_json::Value> {
+    let url = if let Some(domain) = did.strip_prefix("did:web:") {
+        format!("https://{}/.well-known/did.json", domain)
+    } else if did.starts_with("did:plc:") {
+        format!("https://plc.directory/{}", did)
+    } else {
+        anyhow::bail!("unsupported DID method: {}", did);
+    };
+
+    let response = reqwest::get(&url).await?.error_for_status()?;
+    Ok(response.json().await?)
+}
 
+/// Find the user's PDS endpoint in their DID document
+pub fn pds_endpoint(doc: &serde_json::Value) -> Result<String> {
     let service = doc
         .get("service")
         .and_then(|s| s.as_array())
-        .and_then(|arr| arr.first())
+        .and_then(|arr| {
+            arr.iter()
+                .find(|s| {
+                    s.get("id")
+                        .and_then(|id| id.as_str())
+                        .is_some_and(|id| id.ends_with("#atproto_pds"))
+                })
+                .or_else(|| arr.first())
+        })
         .and_then(|s| s.get("serviceEndpoint"))
         .and_then(|e| e.as_str())
         .ok_or_else(|| anyhow::anyhow!("no PDS found in DID document"))?;
@@ -72,40 +121,190 @@ pub async fn resolve_pds(did: &str) -> Result<String> {
     Ok(service.to_string())
 }
 
+/// Resolve DID to find the user's PDS endpoint
+pub async fn resolve_pds(did: &str) -> Result<String> {
+    pds_endpoint(&resolve_did_doc(did).await?)
+}
+
+/// How much of a repo to fetch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Only fetch blocks since the last synced rev, if we have one
+    Incremental,
+    /// Always download and walk the whole repo
+    Full,
+}
+
 /// Download and process a CAR file from a user's AT Protocol repo
-pub async fn backfill_user(did: &str, pool: &PgPool) -> Result<()> {
-    // resolve DID to PDS endpoint
-    let pds = resolve_pds(did).await?;
+pub async fn backfill_user(did: &str, pool: &PgPool, mode: SyncMode) -> Result<BackfillSummary> {
+    // resolve DID to PDS endpoint and repo signing key
+    let doc = resolve_did_doc(did).await?;
+    let pds = pds_endpoint(&doc)?;
+    let signing_key = SigningKey::from_did_doc(&doc)
+        .with_context(|| format!("failed to get signing key for {}", did))?;
     info!("resolved PDS: {}", pds);
 
-    // download CAR file from PDS
-    let pds_url = format!("{}/xrpc/com.atproto.sync.getRepo?did={}", pds, did);
+    let since = match mode {
+        SyncMode::Incremental => last_synced_rev(pool, did).await?,
+        SyncMode::Full => None,
+    };
+
+    if let Some(since) = &since {
+        info!("fetching repo diff for {} since {}", did, since);
+        let response = get_repo(&pds, did, Some(since.as_str())).await?;
+        let too_big = response
+            .content_length()
+            .is_some_and(|len| len > MAX_DIFF_BYTES);
+        if !too_big {
+            if let Some(summary) = sync_diff(did, pool, body_reader(response), &signing_key).await?
+            {
+                return Ok(summary);
+            }
+        }
+        // a PDS that ignores `since` sends the whole repo
+        info!(
+            "repo diff for {} is over {} bytes, syncing the whole repo",
+            did, MAX_DIFF_BYTES
+        );
+    }
 
     info!("fetching repo for {}", did);
-    let response = reqwest::get(&pds_url)
+    let response = get_repo(&pds, did, None).await?;
+    if let Some(len) = response.content_length() {
+        if len > MAX_REPO_BYTES {
+            anyhow::bail!(
+                "repo is {} bytes, over the {} byte limit",
+                len,
+                MAX_REPO_BYTES
+            );
+        }
+    }
+
+    index_repo(did, pool, body_reader(response), Some(&signing_key)).await
+}
+
+/// Request a repo export, or the diff since `since`, from the PDS
+async fn get_repo(pds: &str, did: &str, since: Option<&str>) -> Result<reqwest::Response> {
+    let pds_url = match since {
+        Some(rev) => format!(
+            "{}/xrpc/com.atproto.sync.getRepo?did={}&since={}",
+            pds, did, rev
+        ),
+        None => format!("{}/xrpc/com.atproto.sync.getRepo?did={}", pds, did),
+    };
+
+    let response = HTTP_CLIENT
+        .get(&pds_url)
+        .send()
         .await
         .context("failed to fetch repo")?;
 
     let status = response.status();
-    let car_bytes = response
-        .bytes()
-        .await
-        .context("failed to read response bytes")?;
-
-    info!("downloaded {} bytes (status: {})", car_bytes.len(), status);
-
     if !status.is_success() {
-        let error_text = String::from_utf8_lossy(&car_bytes);
+        let error_text = response.text().await.unwrap_or_default();
         anyhow::bail!("failed to fetch repo: {} - {}", status, error_text);
     }
 
-    // create an async reader from the bytes
-    let reader = Cursor::new(car_bytes.to_vec());
-    let reader = tokio::io::BufReader::new(reader);
+    Ok(response)
+}
+
+/// Stream a response body straight into the CAR reader rather than buffering it
+fn body_reader(response: reqwest::Response) -> impl AsyncRead + Unpin + Send {
+    StreamReader::new(Box::pin(response.bytes_stream().map_err(io::Error::other)))
+}
+
+/// Index a repo CAR export from disk through the same path as [`backfill_user`].
+/// The DID is taken from the export's commit. With `verify_signature` (the
+/// CLI default) the DID is resolved and the commit signature checked; an
+/// unverified export is only upserted, see [`index_repo`].
+pub async fn import_car(
+    path: &Path,
+    pool: &PgPool,
+    verify_signature: bool,
+) -> Result<BackfillSummary> {
+    let did = car_did(path).await?;
+    info!("{} is a repo export for {}", path.display(), did);
+
+    let signing_key = if verify_signature {
+        let doc = resolve_did_doc(&did).await?;
+        Some(
+            SigningKey::from_did_doc(&doc)
+                .with_context(|| format!("failed to get signing key for {}", did))?,
+        )
+    } else {
+        warn!(
+            "not checking the commit signature of {}; records missing from it won't be removed",
+            path.display()
+        );
+        None
+    };
+
+    let file = File::open(path)
+        .await
+        .with_context(|| format!("failed to open {}", path.display()))?;
+
+    index_repo(&did, pool, file, signing_key.as_ref()).await
+}
+
+/// Read a CAR export up to its commit block and return the commit's DID
+async fn car_did(path: &Path) -> Result<String> {
+    let mut file = File::open(path)
+        .await
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut verifier = CarVerifier::new();
+    let mut buf = vec![0; 64 * 1024];
+
+    loop {
+        if let Some(did) = verifier.commit_did()? {
+            return Ok(did);
+        }
+
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            anyhow::bail!("commit block missing from CAR");
+        }
+        verifier.update(&buf[..n])?;
+    }
+}
 
-    let mut event_count = 0;
-    let mut rsvp_count = 0;
-    let mut profile_count = 0;
+/// Verify and index a full repo CAR read from `car`, then remove rows for
+/// records that are no longer in it. Without a `signing_key` nobody vouches
+/// for the export, so its records are only upserted: nothing is removed and
+/// the synced rev isn't moved.
+pub async fn index_repo<R>(
+    did: &str,
+    pool: &PgPool,
+    car: R,
+    signing_key: Option<&SigningKey>,
+) -> Result<BackfillSummary>
+where
+    R: AsyncRead + Unpin + Send,
+{
+    // blocks are checked against their CIDs as they stream in; the commit
+    // signature is checked once everything has been read, before indexing
+    let verifier = Arc::new(Mutex::new(
+        CarVerifier::new().with_max_bytes(MAX_REPO_BYTES),
+    ));
+    let reader = BufReader::new(VerifyingReader::new(car, verifier.clone()));
+    let verify = |verifier: &Mutex<CarVerifier>| {
+        let commit = verifier
+            .lock()
+            .expect("car verifier lock poisoned")
+            .finish(did, signing_key)
+            .with_context(|| format!("repo verification failed for {}", did))?;
+        info!(
+            "verified repo commit {} (rev {}) for {}",
+            commit.cid, commit.rev, did
+        );
+        anyhow::Ok(commit)
+    };
+
+    // each record is written in its own short transaction, as in live
+    // ingestion, so a big repo never holds locks on everything it touched.
+    // nothing is written until the CAR is downloaded and verified
+    let sink = RecordSink::new(pool.clone());
+    let mut seen = HashSet::new();
+    let mut counts = BackfillSummary::default();
 
     match DriverBuilder::new()
         .with_mem_limit_mb(100)
@@ -114,327 +313,295 @@ pub async fn backfill_user(did: &str, pool: &PgPool) -> Result<()> {
         .await?
     {
         Driver::Memory(_commit, mut driver) => {
+            counts.rev = verify(&verifier)?.rev;
+
             // process records in chunks
             while let Some(chunk) = driver.next_chunk(2048).await? {
-                for (rkey, block_data) in chunk {
-                    if rkey.starts_with(EVENT_COLLECTION) {
-                        match value::from_cbor::<Event>(&block_data) {
-                            Ok(event) => {
-                                let rkey_tail = rkey.split('/').last().unwrap_or(&rkey);
-                                let uri =
-                                    format!("at://{}/{}/{}", did, EVENT_COLLECTION, rkey_tail);
-                                let cid = compute_cid(&block_data)?;
-
-                                let created_at = event.created_at.as_ref();
-                                let starts_at = event.starts_at.as_ref().map(|dt| dt.as_ref());
-                                let ends_at = event.ends_at.as_ref().map(|dt| dt.as_ref());
-                                let locations = event
-                                    .locations
-                                    .as_ref()
-                                    .map(|locs| serde_json::to_value(locs))
-                                    .transpose()?;
-                                let uris = event
-                                    .uris
-                                    .as_ref()
-                                    .map(|uris| serde_json::to_value(uris))
-                                    .transpose()?;
-
-                                if let Err(e) = sqlx::query!(
-                                        r#"
-                                        INSERT INTO events (uri, cid, did, rkey, name, description, created_at, starts_at, ends_at, mode, status, locations, uris)
-                                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
-                                        ON CONFLICT (uri) DO UPDATE SET
-                                            cid = EXCLUDED.cid,
-                                            name = EXCLUDED.name,
-                                            description = EXCLUDED.description,
-                                            created_at = EXCLUDED.created_at,
-                                            starts_at = EXCLUDED.starts_at,
-                                            ends_at = EXCLUDED.ends_at,
-                                            mode = EXCLUDED.mode,
-                                            status = EXCLUDED.status,
-                                            locations = EXCLUDED.locations,
-                                            uris = EXCLUDED.uris
-                                        "#,
-                                        uri,
-                                        cid,
-                                        did,
-                                        rkey_tail,
-                                        event.name.as_ref(),
-                                        event.description.as_ref().map(|d| d.as_ref()),
-                                        created_at,
-                                        starts_at,
-                                        ends_at,
-                                        event.mode.as_ref().map(|m| m.as_ref()),
-                                        event.status.as_ref().map(|s| s.as_ref()),
-                                        locations,
-                                        uris,
-                                    )
-                                    .execute(pool)
-                                    .await {
-                                        warn!("failed to insert event {}: {}", uri, e);
-                                    } else {
-                                        event_count += 1;
-                                    }
-                            }
-                            Err(e) => warn!("failed to parse event from {}: {}", rkey, e),
-                        }
-                    } else if rkey.starts_with(RSVP_COLLECTION) {
-                        match value::from_cbor::<Rsvp>(&block_data) {
-                            Ok(rsvp) => {
-                                let rkey_tail = rkey.split('/').last().unwrap_or(&rkey);
-                                let uri = format!("at://{}/{}/{}", did, RSVP_COLLECTION, rkey_tail);
-                                let cid = compute_cid(&block_data)?;
-
-                                // Extract uri and cid from the strongRef Data
-                                let (subject_uri, subject_cid) = match &rsvp.subject {
-                                    value::Data::Object(obj) => {
-                                        let uri = obj.get("uri").and_then(|v| match v {
-                                            value::Data::String(s) => Some(s.as_ref()),
-                                            _ => None,
-                                        });
-                                        let cid = obj.get("cid").and_then(|v| match v {
-                                            value::Data::String(s) => Some(s.as_ref()),
-                                            _ => None,
-                                        });
-                                        (uri, cid)
-                                    }
-                                    _ => (None, None),
-                                };
-
-                                if let Err(e) = sqlx::query!(
-                                        r#"
-                                        INSERT INTO rsvps (uri, cid, did, rkey, subject_uri, subject_cid, status)
-                                        VALUES ($1, $2, $3, $4, $5, $6, $7)
-                                        ON CONFLICT (uri) DO UPDATE SET
-                                            cid = EXCLUDED.cid,
-                                            subject_uri = EXCLUDED.subject_uri,
-                                            subject_cid = EXCLUDED.subject_cid,
-                                            status = EXCLUDED.status
-                                        "#,
-                                        uri,
-                                        cid,
-                                        did,
-                                        rkey_tail,
-                                        subject_uri,
-                                        subject_cid,
-                                        rsvp.status.as_ref(),
-                                    )
-                                    .execute(pool)
-                                    .await {
-                                        warn!("failed to insert rsvp {}: {}", uri, e);
-                                    } else {
-                                        rsvp_count += 1;
-                                    }
-                            }
-                            Err(e) => warn!("failed to parse rsvp from {} (inmem): {}", rkey, e),
-                        }
-                    } else if rkey.starts_with(PROFILE_COLLECTION) {
-                        match value::from_cbor::<Profile>(&block_data) {
-                                Ok(profile) => {
-                                    if let Err(e) = sqlx::query!(
-                                        r#"
-                                        INSERT INTO profiles (did, display_name, description, avatar, banner)
-                                        VALUES ($1, $2, $3, $4, $5)
-                                        ON CONFLICT (did) DO UPDATE SET
-                                            display_name = EXCLUDED.display_name,
-                                            description = EXCLUDED.description,
-                                            avatar = EXCLUDED.avatar,
-                                            banner = EXCLUDED.banner,
-                                            updated_at = NOW()
-                                        "#,
-                                        did,
-                                        profile.display_name.as_ref().map(|n| n.as_ref()),
-                                        profile.description.as_ref().map(|d| d.as_ref()),
-                                        profile.avatar.as_ref().map(|_| "blob_ref"),
-                                        profile.banner.as_ref().map(|_| "blob_ref"),
-                                    )
-                                    .execute(pool)
-                                    .await {
-                                        warn!("failed to insert profile for {}: {}", did, e);
-                                    } else {
-                                        profile_count += 1;
-                                    }
-                                }
-                                Err(e) => warn!("failed to parse profile from {}: {}", rkey, e),
-                            }
-                    }
-                }
+                index_chunk(&sink, did, chunk, &mut counts, &mut seen).await?;
             }
         }
         Driver::Disk(paused) => {
             info!("repo {} exceeds memory limit, using disk storage", did);
 
-            // create temporary directory for disk storage
-            let temp_dir = std::env::temp_dir().join(format!("repo-{}", did.replace(':', "-")));
-            std::fs::create_dir_all(&temp_dir)?;
+            // a fresh directory per run, so concurrent syncs of the same repo
+            // don't share a store; it's removed when dropped, on error too
+            let temp_dir = tempfile::Builder::new()
+                .prefix(&format!("repo-{}-", did.replace(':', "-")))
+                .tempdir()?;
 
-            let disk_path = temp_dir.join("blocks.db");
+            let disk_path = temp_dir.path().join("blocks.db");
             let store = DiskBuilder::new().open(disk_path).await?;
 
             let (_commit, mut driver) = paused.finish_loading(store).await?;
+            counts.rev = verify(&verifier)?.rev;
 
             // process records in chunks from disk
             while let Some(chunk) = driver.next_chunk(256).await? {
-                for (rkey, block_data) in chunk {
-                    if rkey.starts_with(EVENT_COLLECTION) {
-                        match value::from_cbor::<Event>(&block_data) {
-                            Ok(event) => {
-                                let rkey_tail = rkey.split('/').last().unwrap_or(&rkey);
-                                let uri =
-                                    format!("at://{}/{}/{}", did, EVENT_COLLECTION, rkey_tail);
-                                let cid = compute_cid(&block_data)?;
-
-                                let created_at = event.created_at.as_ref();
-                                let starts_at = event.starts_at.as_ref().map(|dt| dt.as_ref());
-                                let ends_at = event.ends_at.as_ref().map(|dt| dt.as_ref());
-                                let locations = event
-                                    .locations
-                                    .as_ref()
-                                    .map(|locs| serde_json::to_value(locs))
-                                    .transpose()?;
-                                let uris = event
-                                    .uris
-                                    .as_ref()
-                                    .map(|uris| serde_json::to_value(uris))
-                                    .transpose()?;
-
-                                if let Err(e) = sqlx::query!(
-                                        r#"
-                                        INSERT INTO events (uri, cid, did, rkey, name, description, created_at, starts_at, ends_at, mode, status, locations, uris)
-                                        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
-                                        ON CONFLICT (uri) DO UPDATE SET
-                                            cid = EXCLUDED.cid,
-                                            name = EXCLUDED.name,
-                                            description = EXCLUDED.description,
-                                            created_at = EXCLUDED.created_at,
-                                            starts_at = EXCLUDED.starts_at,
-                                            ends_at = EXCLUDED.ends_at,
-                                            mode = EXCLUDED.mode,
-                                            status = EXCLUDED.status,
-                                            locations = EXCLUDED.locations,
-                                            uris = EXCLUDED.uris
-                                        "#,
-                                        uri,
-                                        cid,
-                                        did,
-                                        rkey_tail,
-                                        event.name.as_ref(),
-                                        event.description.as_ref().map(|d| d.as_ref()),
-                                        created_at,
-                                        starts_at,
-                                        ends_at,
-                                        event.mode.as_ref().map(|m| m.as_ref()),
-                                        event.status.as_ref().map(|s| s.as_ref()),
-                                        locations,
-                                        uris,
-                                    )
-                                    .execute(pool)
-                                    .await {
-                                        warn!("failed to insert event {}: {}", uri, e);
-                                    } else {
-                                        event_count += 1;
-                                    }
-                            }
-                            Err(e) => warn!("failed to parse event from {}: {}", rkey, e),
-                        }
-                    } else if rkey.starts_with(RSVP_COLLECTION) {
-                        match value::from_cbor::<Rsvp>(&block_data) {
-                            Ok(rsvp) => {
-                                let rkey_tail = rkey.split('/').last().unwrap_or(&rkey);
-                                let uri = format!("at://{}/{}/{}", did, RSVP_COLLECTION, rkey_tail);
-                                let cid = compute_cid(&block_data)?;
-
-                                // Extract uri and cid from the strongRef Data
-                                let (subject_uri, subject_cid) = match &rsvp.subject {
-                                    value::Data::Object(obj) => {
-                                        let uri = obj.get("uri").and_then(|v| match v {
-                                            value::Data::String(s) => Some(s.as_ref()),
-                                            _ => None,
-                                        });
-                                        let cid = obj.get("cid").and_then(|v| match v {
-                                            value::Data::String(s) => Some(s.as_ref()),
-                                            _ => None,
-                                        });
-                                        (uri, cid)
-                                    }
-                                    _ => (None, None),
-                                };
-
-                                if let Err(e) = sqlx::query!(
-                                        r#"
-                                        INSERT INTO rsvps (uri, cid, did, rkey, subject_uri, subject_cid, status)
-                                        VALUES ($1, $2, $3, $4, $5, $6, $7)
-                                        ON CONFLICT (uri) DO UPDATE SET
-                                            cid = EXCLUDED.cid,
-                                            subject_uri = EXCLUDED.subject_uri,
-                                            subject_cid = EXCLUDED.subject_cid,
-                                            status = EXCLUDED.status
-                                        "#,
-                                        uri,
-                                        cid,
-                                        did,
-                                        rkey_tail,
-                                        subject_uri,
-                                        subject_cid,
-                                        rsvp.status.as_ref(),
-                                    )
-                                    .execute(pool)
-                                    .await {
-                                        warn!("failed to insert rsvp {}: {}", uri, e);
-                                    } else {
-                                        rsvp_count += 1;
-                                    }
-                            }
-                            Err(e) => warn!("failed to parse rsvp from {} (disk): {}", rkey, e),
-                        }
-                    } else if rkey.starts_with(PROFILE_COLLECTION) {
-                        match value::from_cbor::<Profile>(&block_data) {
-                            Ok(profile) => {
-                                if let Err(e) = sqlx::query!(
-                                    r#"
-                                    INSERT INTO profiles (did, display_name, description, avatar, banner)
-                                    VALUES ($1, $2, $3, $4, $5)
-                                    ON CONFLICT (did) DO UPDATE SET
-                                        display_name = EXCLUDED.display_name,
-                                        description = EXCLUDED.description,
-                                        avatar = EXCLUDED.avatar,
-                                        banner = EXCLUDED.banner,
-                                        updated_at = NOW()
-                                    "#,
-                                    did,
-                                    profile.display_name.as_ref().map(|n| n.as_ref()),
-                                    profile.description.as_ref().map(|d| d.as_ref()),
-                                    profile.avatar.as_ref().map(|_| "blob_ref"),
-                                    profile.banner.as_ref().map(|_| "blob_ref"),
-                                )
-                                .execute(pool)
-                                .await {
-                                    warn!("failed to insert profile for {}: {}", did, e);
-                                } else {
-                                    profile_count += 1;
-                                }
-                            }
-                            Err(e) => warn!("failed to parse profile from {}: {}", rkey, e),
-                        }
-                    }
-                }
+                index_chunk(&sink, did, chunk, &mut counts, &mut seen).await?;
             }
 
-            // clean up temporary directory
-            if let Err(e) = std::fs::remove_dir_all(&temp_dir) {
+            drop(driver);
+            if let Err(e) = temp_dir.close() {
                 warn!("failed to clean up temp dir: {}", e);
             }
         }
     }
 
+    // removals and the synced rev land together, once every record is in
+    if signing_key.is_some() {
+        let mut tx = pool.begin().await?;
+        counts.removed = remove_missing(&mut tx, did, &counts.rev, &seen).await?;
+        save_synced_rev(&mut tx, did, &counts.rev).await?;
+        tx.commit().await?;
+    }
+
+    info!(
+        "backfill complete: {} events, {} rsvps, {} profiles, {} removed",
+        counts.events, counts.rsvps, counts.profiles, counts.removed
+    );
+
+    Ok(counts)
+}
+
+/// Index the records in a `since` diff of a repo. Returns `None` without
+/// indexing anything if the diff is over [`MAX_DIFF_BYTES`].
+async fn sync_diff<R>(
+    did: &str,
+    pool: &PgPool,
+    car: R,
+    signing_key: &SigningKey,
+) -> Result<Option<BackfillSummary>>
+where
+    R: AsyncRead + Unpin,
+{
+    // diffs are small, so their blocks are kept in memory for the MST walk
+    let verifier = Arc::new(Mutex::new(
+        CarVerifier::new()
+            .keep_blocks()
+            .with_max_bytes(MAX_DIFF_BYTES),
+    ));
+    let mut reader = VerifyingReader::new(car, verifier.clone());
+    let read = tokio::io::copy(&mut reader, &mut tokio::io::sink()).await;
+
+    let mut verifier = verifier.lock().expect("car verifier lock poisoned");
+    if verifier.over_limit() {
+        return Ok(None);
+    }
+    read.context("failed to read repo diff")?;
+    let commit = verifier
+        .finish(did, Some(signing_key))
+        .with_context(|| format!("repo verification failed for {}", did))?;
+    let blocks = verifier.take_blocks();
+    drop(verifier);
+    info!(
+        "verified repo diff {} (rev {}) for {}",
+        commit.cid, commit.rev, did
+    );
+
+    // deletes aren't visible in a diff, so nothing is reconciled here; live
+    // ingestion or the next full sync picks them up
+    let chunk = mst::changed_records(commit.data, &blocks)?
+        .into_iter()
+        .filter_map(|(path, cid)| blocks.get(&cid).map(|block| (path, block.clone())))
+        .collect();
+
+    let sink = RecordSink::new(pool.clone());
+    let mut counts = BackfillSummary {
+        rev: commit.rev,
+        incremental: true,
+        ..Default::default()
+    };
+    index_chunk(&sink, did, chunk, &mut counts, &mut HashSet::new()).await?;
+
+    let mut conn = pool.acquire().await?;
+    save_synced_rev(&mut conn, did, &counts.rev).await?;
+
     info!(
-        "backfill complete: {} events, {} rsvps, {} profiles",
-        event_count, rsvp_count, profile_count
+        "incremental sync complete: {} events, {} rsvps, {} profiles",
+        counts.events, counts.rsvps, counts.profiles
     );
 
+    Ok(Some(counts))
+}
+
+/// The repo rev we last synced for a DID
+pub async fn last_synced_rev(pool: &PgPool, did: &str) -> Result<Option<String>> {
+    let rev = sqlx::query_scalar!("SELECT rev FROM repo_revs WHERE did = $1", did)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(rev)
+}
+
+async fn save_synced_rev(conn: &mut PgConnection, did: &str, rev: &str) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO repo_revs (did, rev)
+        VALUES ($1, $2)
+        ON CONFLICT (did) DO UPDATE SET
+            rev = EXCLUDED.rev,
+            synced_at = NOW()
+        "#,
+        did,
+        rev
+    )
+    .execute(&mut *conn)
+    .await?;
+
     Ok(())
 }
 
+/// What a single repo backfill indexed
+#[derive(Debug, Default)]
+pub struct BackfillSummary {
+    /// The repo rev of the verified commit
+    pub rev: String,
+    /// Whether only a `since` diff was fetched
+    pub incremental: bool,
+    pub events: usize,
+    pub rsvps: usize,
+    pub profiles: usize,
+    /// Rows for the DID that were no longer in the repo and got deleted
+    pub removed: u64,
+}
+
+/// Send a chunk of `(collection/rkey, block)` pairs from the MST walk to the
+/// sink, recording the uri of every wanted record in `seen`. The records are
+/// written as of `counts.rev`, so rows from a newer commit are left alone.
+/// Records that fail are stored in `failed_records` by the sink.
+async fn index_chunk(
+    sink: &RecordSink,
+    did: &str,
+    chunk: Vec<(String, Vec<u8>)>,
+    counts: &mut BackfillSummary,
+    seen: &mut HashSet<String>,
+) -> Result<()> {
+    let rev = counts.rev.clone();
+    for (path, block_data) in chunk {
+        let Some((collection, rkey)) = path.split_once('/') else {
+            continue;
+        };
+        if !WANTED_COLLECTIONS.contains(&collection) {
+            continue;
+        }
+
+        let cid = compute_cid(&block_data)?;
+        let op = RecordOp {
+            did,
+            collection,
+            rkey,
+            cid: Some(&cid),
+            rev: Some(&rev),
+            record: Some(RawRecord::Cbor(&block_data)),
+        };
+        // records that fail to index still exist in the repo, so the rows we
+        // already have for them are kept
+        seen.insert(op.uri());
+
+        match sink.apply(op).await {
+            Ok(Applied::Upserted(RecordKind::Event)) => counts.events += 1,
+            Ok(Applied::Upserted(RecordKind::Rsvp)) => counts.rsvps += 1,
+            Ok(Applied::Upserted(RecordKind::Profile)) => counts.profiles += 1,
+            Ok(_) => {}
+            Err(e) => warn!("failed to index {}: {:#}", path, e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Delete every indexed row for `did` whose record wasn't seen in a full repo
+/// walk at `rev`. Rows written at a later rev were created after the export
+/// and are kept.
+async fn remove_missing(
+    conn: &mut PgConnection,
+    did: &str,
+    rev: &str,
+    seen: &HashSet<String>,
+) -> Result<u64> {
+    let seen: Vec<String> = seen.iter().cloned().collect();
+    let profile_uri = format!("at://{}/{}/self", did, PROFILE_COLLECTION);
+    let has_profile = seen.contains(&profile_uri);
+
+    let events = sqlx::query!(
+        "DELETE FROM events WHERE did = $1 AND uri <> ALL($2) AND (rev IS NULL OR rev <= $3)",
+        did,
+        &seen,
+        rev
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    // revisions of events we still have stay
+    sqlx::query!(
+        r#"
+        DELETE FROM event_revisions er
+        WHERE er.did = $1
+          AND NOT EXISTS (SELECT 1 FROM events e WHERE e.uri = er.uri)
+        "#,
+        did
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    // failures for records that are gone would otherwise come back on replay
+    sqlx::query!(
+        "DELETE FROM failed_records WHERE did = $1 AND uri <> ALL($2) AND (rev IS NULL OR rev <= $3)",
+        did,
+        &seen,
+        rev
+    )
+    .execute(&mut *conn)
+    .await?;
+    sqlx::query!(
+        "DELETE FROM quarantined_records WHERE did = $1 AND uri <> ALL($2) AND (rev IS NULL OR rev <= $3)",
+        did,
+        &seen,
+        rev
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let rsvps = sqlx::query!(
+        "DELETE FROM rsvps WHERE did = $1 AND uri <> ALL($2) AND (rev IS NULL OR rev <= $3)",
+        did,
+        &seen,
+        rev
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    let profiles = if has_profile {
+        0
+    } else {
+        sqlx::query!(
+            "DELETE FROM profiles WHERE did = $1 AND (rev IS NULL OR rev <= $2)",
+            did,
+            rev
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected()
+    };
+
+    let bsky_profile_uri = format!("at://{}/{}/self", did, BSKY_PROFILE_COLLECTION);
+    if !seen.contains(&bsky_profile_uri) {
+        sink::clear_bsky_profile(conn, did, Some(rev)).await?;
+    }
+
+    let removed = events + rsvps + profiles;
+    if removed > 0 {
+        info!(
+            "removed {} events, {} rsvps, {} profiles no longer in {}'s repo",
+            events, rsvps, profiles, did
+        );
+    }
+
+    Ok(removed)
+}
+
 fn compute_cid(block_data: &[u8]) -> Result<String> {
     use multihash::Multihash;
     use sha2::{Digest, Sha256};
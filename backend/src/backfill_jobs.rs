@@ -0,0 +1,410 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use moka::future::Cache;
+use sqlx::PgPool;
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::backfill::{self, SyncMode};
+
+/// Jobs are given up on after this many failed attempts
+pub const MAX_ATTEMPTS: i32 = 5;
+
+/// A running job whose worker hasn't reported back in this long is assumed
+/// to have crashed and may be claimed again
+const STALE_AFTER: Duration = Duration::from_secs(30 * 60);
+
+/// How often a worker touches its running job, well inside `STALE_AFTER`
+/// so a slow download or a big repo isn't mistaken for a dead worker
+const HEARTBEAT_EVERY: Duration = Duration::from_secs(60);
+
+/// Longest a worker sleeps waiting for a failed job's retry, so jobs queued
+/// in the meantime aren't left waiting as long
+const RETRY_POLL: Duration = Duration::from_secs(30);
+
+const BACKOFF_BASE: Duration = Duration::from_secs(30);
+const BACKOFF_MAX: Duration = Duration::from_secs(3600);
+
+/// At most this many newly seen accounts are queued per minute; the rest are
+/// picked up the next time they post once the window resets
+const AUTO_ENQUEUE_PER_MINUTE: u32 = 60;
+
+/// A claimed backfill job
+#[derive(Debug)]
+pub struct BackfillJob {
+    pub did: String,
+    pub attempts: i32,
+}
+
+/// Row counts per job state
+#[derive(Debug, Default)]
+pub struct QueueStatus {
+    pub pending: i64,
+    pub running: i64,
+    pub done: i64,
+    pub failed: i64,
+    /// failed jobs that have used up all their attempts
+    pub exhausted: i64,
+}
+
+/// Add DIDs to the queue. DIDs that already have a job are left alone.
+/// Returns the number of new jobs.
+pub async fn enqueue(pool: &PgPool, dids: &[String]) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO backfill_jobs (did)
+        SELECT * FROM UNNEST($1::text[])
+        ON CONFLICT (did) DO NOTHING
+        "#,
+        dids
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Queues backfills for accounts that show up in live ingestion before we've
+/// ever backfilled them, so their older records and the events their rsvps
+/// point at get indexed without anyone running the CLI
+#[derive(Clone)]
+pub struct AutoBackfill {
+    pool: PgPool,
+    // DIDs we know already have a job, so most records skip the db check
+    known: Cache<String, ()>,
+    // (start of the current window, accounts queued in it)
+    window: Arc<Mutex<(Instant, u32)>>,
+}
+
+impl AutoBackfill {
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            known: Cache::builder()
+                .max_capacity(100_000)
+                .time_to_live(Duration::from_secs(3600))
+                .build(),
+            window: Arc::new(Mutex::new((Instant::now(), 0))),
+        }
+    }
+
+    /// Queue a backfill for `did` if it has never had one
+    pub async fn request(&self, did: &str) {
+        if self.known.contains_key(did) {
+            return;
+        }
+
+        if let Err(e) = self.try_request(did).await {
+            warn!("failed to queue backfill for {}: {}", did, e);
+        }
+    }
+
+    async fn try_request(&self, did: &str) -> Result<()> {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM backfill_jobs WHERE did = $1) AS "exists!""#,
+            did
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if !exists {
+            if !self.take_permit() {
+                debug!("auto backfill rate limited, skipping {} for now", did);
+                return Ok(());
+            }
+
+            if enqueue(&self.pool, &[did.to_string()]).await? > 0 {
+                info!("queued backfill for newly seen account {}", did);
+            }
+        }
+
+        self.known.insert(did.to_string(), ()).await;
+        Ok(())
+    }
+
+    fn take_permit(&self) -> bool {
+        let mut window = self.window.lock().expect("auto backfill lock poisoned");
+        if window.0.elapsed() >= Duration::from_secs(60) {
+            *window = (Instant::now(), 0);
+        }
+
+        if window.1 >= AUTO_ENQUEUE_PER_MINUTE {
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+}
+
+/// Claim the next runnable job, skipping rows other workers hold
+pub async fn claim(pool: &PgPool) -> Result<Option<BackfillJob>> {
+    // a job whose worker died on its last attempt would otherwise stay
+    // running forever
+    sqlx::query!(
+        r#"
+        UPDATE backfill_jobs
+        SET state = 'failed',
+            last_error = COALESCE(last_error, 'worker stopped responding'),
+            finished_at = NOW(),
+            updated_at = NOW()
+        WHERE state = 'running'
+          AND updated_at < NOW() - make_interval(secs => $2)
+          AND attempts >= $1
+        "#,
+        MAX_ATTEMPTS,
+        STALE_AFTER.as_secs() as f64
+    )
+    .execute(pool)
+    .await?;
+
+    let job = sqlx::query_as!(
+        BackfillJob,
+        r#"
+        UPDATE backfill_jobs
+        SET state = 'running',
+            attempts = attempts + 1,
+            started_at = NOW(),
+            updated_at = NOW()
+        WHERE did = (
+            SELECT did
+            FROM backfill_jobs
+            WHERE attempts < $1
+              AND ((state IN ('pending', 'failed') AND run_after <= NOW())
+                   OR (state = 'running' AND updated_at < NOW() - make_interval(secs => $2)))
+            ORDER BY run_after
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING did, attempts
+        "#,
+        MAX_ATTEMPTS,
+        STALE_AFTER.as_secs() as f64
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(job)
+}
+
+/// Mark a job as done, recording the repo rev we indexed
+pub async fn complete(pool: &PgPool, did: &str, rev: &str) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE backfill_jobs
+        SET state = 'done',
+            repo_rev = $2,
+            last_error = NULL,
+            finished_at = NOW(),
+            updated_at = NOW()
+        WHERE did = $1
+        "#,
+        did,
+        rev
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Mark a job as failed and schedule its retry with exponential backoff
+pub async fn fail(pool: &PgPool, job: &BackfillJob, err: &str) -> Result<()> {
+    let backoff = backoff(job.attempts);
+
+    sqlx::query!(
+        r#"
+        UPDATE backfill_jobs
+        SET state = 'failed',
+            last_error = $2,
+            run_after = NOW() + make_interval(secs => $3),
+            finished_at = NOW(),
+            updated_at = NOW()
+        WHERE did = $1
+        "#,
+        &job.did,
+        err,
+        backoff.as_secs() as f64
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Move exhausted failed jobs back to pending with a fresh attempt budget
+pub async fn retry_failed(pool: &PgPool) -> Result<u64> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE backfill_jobs
+        SET state = 'pending', attempts = 0, run_after = NOW(), updated_at = NOW()
+        WHERE state = 'failed' AND attempts >= $1
+        "#,
+        MAX_ATTEMPTS
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn status(pool: &PgPool) -> Result<QueueStatus> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT state, attempts >= $1 as "exhausted!", COUNT(*) as "count!"
+        FROM backfill_jobs
+        GROUP BY 1, 2
+        "#,
+        MAX_ATTEMPTS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut status = QueueStatus::default();
+    for row in rows {
+        match row.state.as_str() {
+            "pending" => status.pending += row.count,
+            "running" => status.running += row.count,
+            "done" => status.done += row.count,
+            "failed" => {
+                status.failed += row.count;
+                if row.exhausted {
+                    status.exhausted += row.count;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(status)
+}
+
+/// The most recent failures, newest first
+pub async fn recent_failures(pool: &PgPool, limit: i64) -> Result<Vec<(String, i32, String)>> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT did, attempts, last_error as "last_error!"
+        FROM backfill_jobs
+        WHERE state = 'failed' AND last_error IS NOT NULL
+        ORDER BY updated_at DESC
+        LIMIT $1
+        "#,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|r| (r.did, r.attempts, r.last_error))
+        .collect())
+}
+
+/// Totals for one worker's run
+#[derive(Debug, Default)]
+pub struct WorkerStats {
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Rows deleted because their records were gone from the repo
+    pub removed: u64,
+}
+
+/// When the next job that isn't runnable yet but still has attempts left
+/// comes due
+async fn next_retry(pool: &PgPool) -> Result<Option<DateTime<Utc>>> {
+    let next = sqlx::query_scalar!(
+        r#"
+        SELECT MIN(run_after)
+        FROM backfill_jobs
+        WHERE state IN ('pending', 'failed') AND attempts < $1
+        "#,
+        MAX_ATTEMPTS
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(next)
+}
+
+/// Claim and run jobs until none are left to run, waiting out the backoff of
+/// failed jobs that still have attempts left
+pub async fn run_worker(pool: &PgPool, mode: SyncMode) -> Result<WorkerStats> {
+    let mut stats = WorkerStats::default();
+
+    loop {
+        let Some(job) = claim(pool).await? else {
+            let Some(next) = next_retry(pool).await? else {
+                break;
+            };
+            let wait = (next - Utc::now()).to_std().unwrap_or_default();
+            debug!("waiting {:?} for the next backfill retry", wait);
+            tokio::time::sleep(wait.min(RETRY_POLL)).await;
+            continue;
+        };
+
+        let result = tokio::select! {
+            result = backfill::backfill_user(&job.did, pool, mode) => result,
+            _ = heartbeat(pool, &job.did) => unreachable!("heartbeat never returns"),
+        };
+
+        match result {
+            Ok(summary) => {
+                complete(pool, &job.did, &summary.rev).await?;
+                stats.succeeded += 1;
+                stats.removed += summary.removed;
+                info!("backfill complete for {}", job.did);
+            }
+            Err(e) => {
+                let err = format!("{:#}", e);
+                fail(pool, &job, &err).await?;
+                stats.failed += 1;
+                error!(
+                    "backfill failed for {} (attempt {}/{}): {}",
+                    job.did, job.attempts, MAX_ATTEMPTS, err
+                );
+            }
+        }
+    }
+
+    Ok(stats)
+}
+
+/// Keep a running job's `updated_at` fresh until the caller drops this
+async fn heartbeat(pool: &PgPool, did: &str) {
+    let mut interval = tokio::time::interval(HEARTBEAT_EVERY);
+    // the first tick fires immediately, and the job was just claimed
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let touched = sqlx::query!(
+            r#"
+            UPDATE backfill_jobs
+            SET updated_at = NOW()
+            WHERE did = $1 AND state = 'running'
+            "#,
+            did
+        )
+        .execute(pool)
+        .await;
+        if let Err(e) = touched {
+            warn!("failed to touch backfill job for {}: {}", did, e);
+        }
+    }
+}
+
+pub(crate) fn backoff(attempts: i32) -> Duration {
+    let exp = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(exp))
+        .min(BACKOFF_MAX)
+}
+
+#[test]
+fn test_backoff() {
+    assert_eq!(backoff(1), Duration::from_secs(30));
+    assert_eq!(backoff(2), Duration::from_secs(60));
+    assert_eq!(backoff(3), Duration::from_secs(120));
+    assert_eq!(backoff(20), BACKOFF_MAX);
+}
@@ -0,0 +1,186 @@
+use anyhow::Result;
+use moka::future::Cache;
+use serde_json::Value;
+use std::time::Duration;
+
+use crate::{backfill::resolve_pds, image_proxy::Preset};
+
+/// A blob referenced from a record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobRef {
+    pub cid: String,
+    pub mime_type: String,
+}
+
+impl BlobRef {
+    /// Read the blob ref in `field` of a record's json. Handles both the
+    /// current `{"ref": {"$link": ...}}` shape and the legacy `{"cid": ...}` one.
+    pub fn from_record(record: &Value, field: &str) -> Option<Self> {
+        let blob = record.get(field)?;
+        let cid = blob
+            .get("ref")
+            .and_then(|r| r.get("$link"))
+            .or_else(|| blob.get("cid"))
+            .and_then(|c| c.as_str())?;
+        let mime_type = blob.get("mimeType").and_then(|m| m.as_str())?;
+
+        Some(Self {
+            cid: cid.to_string(),
+            mime_type: mime_type.to_string(),
+        })
+    }
+}
+
+/// What a blob is used for, which picks the image size and CDN preset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlobKind {
+    Avatar,
+    Banner,
+}
+
+impl BlobKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BlobKind::Avatar => "avatar",
+            BlobKind::Banner => "banner",
+        }
+    }
+
+    /// The image proxy size variant to serve it at
+    pub fn preset(&self) -> Preset {
+        match self {
+            BlobKind::Avatar => Preset::Thumbnail,
+            BlobKind::Banner => Preset::Full,
+        }
+    }
+}
+
+/// Builds public URLs for blobs from a template with `{kind}`, `{preset}`,
+/// `{did}` and `{cid}` placeholders. By default they point at our own image
+/// proxy; set `BLOB_CDN_URL` (e.g.
+/// `https://cdn.bsky.app/img/{kind}/plain/{did}/{cid}@jpeg`) to use a CDN.
+#[derive(Clone)]
+pub struct BlobUrls {
+    template: String,
+}
+
+impl BlobUrls {
+    pub fn new(template: impl Into<String>) -> Self {
+        Self {
+            template: template.into(),
+        }
+    }
+
+    pub fn from_env(public_url: &str) -> Self {
+        Self::new(std::env::var("BLOB_CDN_URL").unwrap_or_else(|_| {
+            format!(
+                "{}/img/{{preset}}/{{did}}/{{cid}}",
+                public_url.trim_end_matches('/')
+            )
+        }))
+    }
+
+    /// URL for blob `cid` in `did`'s repo
+    pub fn url(&self, did: &str, cid: &str, kind: BlobKind) -> String {
+        self.template
+            .replace("{kind}", kind.as_str())
+            .replace("{preset}", kind.preset().as_str())
+            .replace("{did}", did)
+            .replace("{cid}", cid)
+    }
+
+    /// [`url`](Self::url) for an optional blob column
+    pub fn url_opt(&self, did: &str, cid: Option<&str>, kind: BlobKind) -> Option<String> {
+        cid.map(|cid| self.url(did, cid, kind))
+    }
+}
+
+/// The blob cid in a Bluesky CDN image URL (`.../img/{kind}/plain/{did}/{cid}@jpeg`)
+pub fn bsky_cdn_cid(url: &str) -> Option<String> {
+    let (_, path) = url.split_once("/plain/")?;
+    let (_, cid) = path.split_once('/')?;
+    let cid = cid.split_once('@').map_or(cid, |(cid, _)| cid);
+    (!cid.is_empty()).then(|| cid.to_string())
+}
+
+/// Caches DID to PDS endpoint lookups
+#[derive(Clone)]
+pub struct PdsResolver {
+    cache: Cache<String, String>,
+}
+
+impl Default for PdsResolver {
+    fn default() -> Self {
+        Self {
+            cache: Cache::builder()
+                .max_capacity(50_000)
+                .time_to_live(Duration::from_secs(6 * 3600))
+                .build(),
+        }
+    }
+}
+
+impl PdsResolver {
+    pub async fn resolve(&self, did: &str) -> Result<String> {
+        self.cache
+            .try_get_with(did.to_string(), resolve_pds(did))
+            .await
+            .map_err(|e| anyhow::anyhow!("failed to resolve PDS for {}: {:#}", did, e))
+    }
+
+    /// Record a PDS endpoint learned some other way, e.g. from a DID document
+    /// fetched for backfill
+    pub async fn remember(&self, did: &str, pds: &str) {
+        self.cache.insert(did.to_string(), pds.to_string()).await;
+    }
+}
+
+#[test]
+fn test_reads_current_and_legacy_blob_refs() {
+    let record = serde_json::json!({
+        "avatar": {
+            "$type": "blob",
+            "ref": { "$link": "bafkreiavatar" },
+            "mimeType": "image/jpeg",
+            "size": 1234,
+        },
+        "banner": { "cid": "bafkreibanner", "mimeType": "image/png" },
+    });
+
+    assert_eq!(
+        BlobRef::from_record(&record, "avatar"),
+        Some(BlobRef {
+            cid: "bafkreiavatar".to_string(),
+            mime_type: "image/jpeg".to_string(),
+        })
+    );
+    assert_eq!(
+        BlobRef::from_record(&record, "banner").map(|b| b.cid),
+        Some("bafkreibanner".to_string())
+    );
+    assert_eq!(BlobRef::from_record(&record, "missing"), None);
+}
+
+#[test]
+fn test_fills_url_template() {
+    let proxy = BlobUrls::new("https://aktivi.example/img/{preset}/{did}/{cid}");
+    assert_eq!(
+        proxy.url("did:plc:abc", "bafkreiavatar", BlobKind::Avatar),
+        "https://aktivi.example/img/thumbnail/did:plc:abc/bafkreiavatar"
+    );
+
+    let cdn = BlobUrls::new("https://cdn.example.com/img/{kind}/plain/{did}/{cid}@jpeg");
+    assert_eq!(
+        cdn.url("did:plc:abc", "bafkreibanner", BlobKind::Banner),
+        "https://cdn.example.com/img/banner/plain/did:plc:abc/bafkreibanner@jpeg"
+    );
+}
+
+#[test]
+fn test_reads_cid_from_bsky_cdn_url() {
+    assert_eq!(
+        bsky_cdn_cid("https://cdn.bsky.app/img/avatar/plain/did:plc:abc/bafkreiavatar@jpeg"),
+        Some("bafkreiavatar".to_string())
+    );
+    assert_eq!(bsky_cdn_cid("https://example.com/avatar.png"), None);
+}
@@ -0,0 +1,211 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+use tracing::{info, warn};
+
+use crate::sink::{self, RawRecord, RecordOp};
+
+/// A record the sink failed on, as stored in `failed_records`
+#[derive(Debug)]
+pub struct FailedRecord {
+    pub uri: String,
+    pub did: String,
+    pub collection: String,
+    pub rkey: String,
+    pub cid: Option<String>,
+    pub rev: Option<String>,
+    /// `json` or `cbor`; `None` for a failed delete
+    pub encoding: Option<String>,
+    pub record: Option<Vec<u8>>,
+    pub error: String,
+    pub failures: i32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+}
+
+impl FailedRecord {
+    /// Rebuild the op that failed, for replaying it through the sink
+    pub fn op(&self) -> Result<RecordOp<'_>> {
+        let record = match (self.encoding.as_deref(), &self.record) {
+            (Some("json"), Some(bytes)) => Some(RawRecord::Json(serde_json::from_slice(bytes)?)),
+            (Some("cbor"), Some(bytes)) => Some(RawRecord::Cbor(bytes)),
+            (None, None) => None,
+            (encoding, _) => anyhow::bail!(
+                "failed record {} has unknown encoding {:?}",
+                self.uri,
+                encoding
+            ),
+        };
+
+        Ok(RecordOp {
+            did: &self.did,
+            collection: &self.collection,
+            rkey: &self.rkey,
+            cid: self.cid.as_deref(),
+            rev: self.rev.as_deref(),
+            record,
+        })
+    }
+
+    /// The raw record as json, converting CBOR blocks to the atproto json
+    /// form. `None` for a failed delete.
+    pub fn record_json(&self) -> Result<Option<Value>> {
+        self.op()?.record.map(|record| record.to_json()).transpose()
+    }
+}
+
+/// Store an op the sink returned an error for. A record that fails again
+/// replaces its earlier body and error and bumps the failure count, unless
+/// the stored failure is from a newer rev.
+pub async fn save(conn: &mut PgConnection, op: &RecordOp<'_>, error: &anyhow::Error) -> Result<()> {
+    let (encoding, record) = match &op.record {
+        Some(RawRecord::Json(json)) => (Some("json"), Some(serde_json::to_vec(json)?)),
+        Some(RawRecord::Cbor(bytes)) => (Some("cbor"), Some(bytes.to_vec())),
+        None => (None, None),
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO failed_records (uri, did, collection, rkey, cid, rev, encoding, record, error)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (uri) DO UPDATE SET
+            cid = EXCLUDED.cid,
+            rev = EXCLUDED.rev,
+            encoding = EXCLUDED.encoding,
+            record = EXCLUDED.record,
+            error = EXCLUDED.error,
+            failures = failed_records.failures + 1,
+            last_failed_at = NOW()
+        WHERE failed_records.rev IS NULL OR failed_records.rev <= EXCLUDED.rev
+        "#,
+        op.uri(),
+        op.did,
+        op.collection,
+        op.rkey,
+        op.cid,
+        op.rev,
+        encoding,
+        record,
+        format!("{:#}", error),
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Forget a stored failure, e.g. once a later version of the record indexed
+pub async fn clear(conn: &mut PgConnection, uri: &str) -> Result<()> {
+    sqlx::query!("DELETE FROM failed_records WHERE uri = $1", uri)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+/// Most recent failures, optionally only for one collection
+pub async fn list(
+    pool: &PgPool,
+    collection: Option<&str>,
+    limit: i64,
+) -> Result<Vec<FailedRecord>> {
+    let rows = sqlx::query_as!(
+        FailedRecord,
+        r#"
+        SELECT uri, did, collection, rkey, cid, rev, encoding, record, error, failures,
+               first_failed_at, last_failed_at
+        FROM failed_records
+        WHERE ($1::text IS NULL OR collection = $1)
+        ORDER BY last_failed_at DESC
+        LIMIT $2
+        "#,
+        collection,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
+
+pub async fn get(pool: &PgPool, uri: &str) -> Result<Option<FailedRecord>> {
+    let row = sqlx::query_as!(
+        FailedRecord,
+        r#"
+        SELECT uri, did, collection, rkey, cid, rev, encoding, record, error, failures,
+               first_failed_at, last_failed_at
+        FROM failed_records
+        WHERE uri = $1
+        "#,
+        uri
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row)
+}
+
+/// What a replay run did
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+    /// Records that went through the sink and were removed from the table
+    pub replayed: usize,
+    /// Records that failed again and were kept
+    pub failed: usize,
+}
+
+/// Send stored failures back through the sink, oldest first. Filters on a
+/// single uri and/or a collection; with neither, everything is replayed.
+pub async fn replay(
+    pool: &PgPool,
+    uri: Option<&str>,
+    collection: Option<&str>,
+) -> Result<ReplaySummary> {
+    let rows = sqlx::query_as!(
+        FailedRecord,
+        r#"
+        SELECT uri, did, collection, rkey, cid, rev, encoding, record, error, failures,
+               first_failed_at, last_failed_at
+        FROM failed_records
+        WHERE ($1::text IS NULL OR uri = $1)
+          AND ($2::text IS NULL OR collection = $2)
+        ORDER BY first_failed_at ASC
+        "#,
+        uri,
+        collection
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut conn = pool.acquire().await?;
+    let mut summary = ReplaySummary::default();
+
+    for failed in &rows {
+        let op = match failed.op() {
+            Ok(op) => op,
+            Err(e) => {
+                warn!("can't replay {}: {:#}", failed.uri, e);
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        match sink::apply(&mut conn, &op).await {
+            // an outdated or ignored result still means there's nothing left
+            // to fix for this record
+            Ok(applied) => {
+                clear(&mut conn, &failed.uri).await?;
+                info!("replayed {}: {:?}", failed.uri, applied);
+                summary.replayed += 1;
+            }
+            Err(e) => {
+                warn!("replay of {} failed again: {:#}", failed.uri, e);
+                save(&mut conn, &op, &e).await?;
+                summary.failed += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
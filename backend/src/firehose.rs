@@ -0,0 +1,436 @@
+use anyhow::{Context, Result};
+use futures::{Stream, StreamExt};
+use ipld_core::cid::Cid;
+use rocketman::{handler::Ingestors, types::event::Event as JetstreamEvent};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio_tungstenite::tungstenite::Message;
+use tracing::{debug, error, info, warn};
+
+use crate::{jetstream::build_ingestors, sink::WANTED_COLLECTIONS, verify::CarVerifier};
+
+/// How often the in-memory cursor is written to the database
+const CURSOR_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far to rewind a persisted cursor on startup, in relay sequence numbers.
+/// Replaying some events twice is harmless since all writes are upserts.
+const CURSOR_REWIND_SEQ: u64 = 1_000;
+
+/// Consumes `com.atproto.sync.subscribeRepos` directly from a relay, decoding
+/// the CBOR frames and CAR slices ourselves instead of trusting Jetstream's
+/// JSON re-encoding. Decoded records are fed to the same ingestors as Jetstream.
+pub struct FirehoseConsumer {
+    endpoint: String,
+    pool: PgPool,
+    // tracks the last sequence number we've processed, shared across reconnects
+    cursor: Arc<Mutex<Option<u64>>>,
+}
+
+impl FirehoseConsumer {
+    /// `endpoint` is the relay host, e.g. `wss://relay1.us-east.bsky.network`
+    pub fn new(endpoint: String, pool: PgPool) -> Self {
+        Self {
+            endpoint,
+            pool,
+            cursor: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub async fn consume(&self) -> Result<()> {
+        let ingestors = build_ingestors(&self.pool);
+
+        // resume from the persisted cursor if we don't have one in memory yet
+        let needs_load = self.cursor.lock().unwrap().is_none();
+        if needs_load {
+            match load_cursor(&self.pool).await {
+                Ok(Some(stored)) => {
+                    let resumed = stored.saturating_sub(CURSOR_REWIND_SEQ);
+                    info!(
+                        "resuming firehose from seq {} (stored: {})",
+                        resumed, stored
+                    );
+                    *self.cursor.lock().unwrap() = Some(resumed);
+                }
+                Ok(None) => info!("no stored firehose cursor, starting at live tail"),
+                Err(e) => warn!("failed to load firehose cursor: {}", e),
+            }
+        }
+
+        let cursor = *self.cursor.lock().unwrap();
+        info!("connecting to firehose");
+        let mut frames = Box::pin(subscribe(&self.endpoint, cursor).await?);
+
+        // periodically persist the cursor so a crash only replays a few events
+        let f_cursor = self.cursor.clone();
+        let f_pool = self.pool.clone();
+        let flusher = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CURSOR_FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let value = *f_cursor.lock().unwrap();
+                if let Some(value) = value {
+                    if let Err(e) = save_cursor(&f_pool, value).await {
+                        warn!("failed to persist firehose cursor: {}", e);
+                    }
+                }
+            }
+        });
+
+        let result: Result<()> = async {
+            while let Some(frame) = frames.next().await {
+                let frame = frame?;
+                let decoded = match decode_frame(&frame) {
+                    Ok(Some(decoded)) => decoded,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn!("failed to decode firehose frame: {}", e);
+                        continue;
+                    }
+                };
+
+                for event in decoded.events {
+                    if let Err(e) = dispatch(&ingestors, event).await {
+                        error!("failed to process message: {}", e);
+                    }
+                }
+
+                *self.cursor.lock().unwrap() = Some(decoded.seq);
+            }
+            Ok(())
+        }
+        .await;
+
+        flusher.abort();
+        self.flush_cursor().await;
+
+        result?;
+        anyhow::bail!("firehose stream closed")
+    }
+
+    /// Write the current in-memory cursor to the database
+    pub async fn flush_cursor(&self) {
+        let value = *self.cursor.lock().unwrap();
+        if let Some(value) = value {
+            match save_cursor(&self.pool, value).await {
+                Ok(()) => debug!("flushed firehose cursor: {}", value),
+                Err(e) => warn!("failed to persist firehose cursor: {}", e),
+            }
+        }
+    }
+}
+
+/// Send a decoded event to the matching ingestor
+async fn dispatch(ingestors: &Ingestors, event: JetstreamEvent<Value>) -> Result<()> {
+    if let Some(commit) = &event.commit {
+        if let Some(ingestor) = ingestors.commits.get(&commit.collection) {
+            ingestor.ingest(event).await?;
+        }
+    } else if event.identity.is_some() {
+        if let Some(ingestor) = &ingestors.identity {
+            ingestor.ingest(event).await?;
+        }
+    } else if event.account.is_some() {
+        if let Some(ingestor) = &ingestors.account {
+            ingestor.ingest(event).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Open a `subscribeRepos` websocket and yield the raw binary frames
+pub async fn subscribe(
+    endpoint: &str,
+    cursor: Option<u64>,
+) -> Result<impl Stream<Item = Result<Vec<u8>>>> {
+    let base = endpoint.trim_end_matches('/');
+    let url = match cursor {
+        Some(c) => format!("{}/xrpc/com.atproto.sync.subscribeRepos?cursor={}", base, c),
+        None => format!("{}/xrpc/com.atproto.sync.subscribeRepos", base),
+    };
+
+    let (ws, _) = tokio_tungstenite::connect_async(&url)
+        .await
+        .with_context(|| format!("failed to connect to {}", url))?;
+
+    Ok(ws.filter_map(|msg| async move {
+        match msg {
+            Ok(Message::Binary(bytes)) => Some(Ok(bytes.to_vec())),
+            Ok(Message::Close(_)) => None,
+            Ok(_) => None,
+            Err(e) => Some(Err(anyhow::anyhow!("firehose websocket error: {}", e))),
+        }
+    }))
+}
+
+/// A decoded firehose frame, converted into Jetstream-shaped events
+#[derive(Debug)]
+pub struct DecodedFrame {
+    pub seq: u64,
+    pub events: Vec<JetstreamEvent<Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FrameHeader {
+    op: i64,
+    t: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorFrame {
+    error: String,
+    message: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommitFrame {
+    seq: u64,
+    repo: String,
+    rev: String,
+    #[serde(with = "serde_bytes")]
+    blocks: Vec<u8>,
+    ops: Vec<RepoOp>,
+    time: String,
+    #[serde(default)]
+    too_big: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepoOp {
+    action: String,
+    path: String,
+    cid: Option<Cid>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdentityFrame {
+    seq: u64,
+    did: String,
+    time: String,
+    handle: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountFrame {
+    seq: u64,
+    did: String,
+    time: String,
+    active: bool,
+    status: Option<String>,
+}
+
+/// Decode a single `subscribeRepos` frame. Returns `None` for frame types we
+/// don't index (`#sync`, `#info`, ...), and only keeps commit ops for
+/// collections in [`WANTED_COLLECTIONS`].
+pub fn decode_frame(frame: &[u8]) -> Result<Option<DecodedFrame>> {
+    let mut reader = Cursor::new(frame);
+    let header: FrameHeader =
+        serde_ipld_dagcbor::from_reader_once(&mut reader).context("invalid frame header")?;
+
+    if header.op == -1 {
+        let err: ErrorFrame = serde_ipld_dagcbor::from_reader_once(&mut reader)?;
+        anyhow::bail!(
+            "firehose error frame: {} ({})",
+            err.error,
+            err.message.unwrap_or_default()
+        );
+    }
+
+    let decoded = match header.t.as_deref() {
+        Some("#commit") => {
+            let commit: CommitFrame = serde_ipld_dagcbor::from_reader_once(&mut reader)?;
+            decode_commit(commit)?
+        }
+        Some("#identity") => {
+            let identity: IdentityFrame = serde_ipld_dagcbor::from_reader_once(&mut reader)?;
+            let event = to_jetstream_event(json!({
+                "did": identity.did,
+                "time_us": time_us(&identity.time),
+                "kind": "identity",
+                "identity": {
+                    "did": identity.did,
+                    "handle": identity.handle,
+                    "seq": identity.seq,
+                    "time": identity.time,
+                },
+            }))?;
+            DecodedFrame {
+                seq: identity.seq,
+                events: vec![event],
+            }
+        }
+        Some("#account") => {
+            let account: AccountFrame = serde_ipld_dagcbor::from_reader_once(&mut reader)?;
+            let event = to_jetstream_event(json!({
+                "did": account.did,
+                "time_us": time_us(&account.time),
+                "kind": "account",
+                "account": {
+                    "active": account.active,
+                    "did": account.did,
+                    "seq": account.seq,
+                    "time": account.time,
+                    "status": account.status,
+                },
+            }))?;
+            DecodedFrame {
+                seq: account.seq,
+                events: vec![event],
+            }
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(decoded))
+}
+
+fn decode_commit(commit: CommitFrame) -> Result<DecodedFrame> {
+    let wanted: Vec<&RepoOp> = commit
+        .ops
+        .iter()
+        .filter(|op| {
+            op.path
+                .split_once('/')
+                .is_some_and(|(collection, _)| WANTED_COLLECTIONS.contains(&collection))
+        })
+        .collect();
+
+    if wanted.is_empty() {
+        return Ok(DecodedFrame {
+            seq: commit.seq,
+            events: Vec::new(),
+        });
+    }
+
+    if commit.too_big {
+        // the relay omitted the blocks; these records will be picked up by backfill
+        warn!(
+            "skipping tooBig commit from {} (seq {})",
+            commit.repo, commit.seq
+        );
+        return Ok(DecodedFrame {
+            seq: commit.seq,
+            events: Vec::new(),
+        });
+    }
+
+    let blocks = read_car_blocks(&commit.repo, &commit.rev, &commit.blocks)?;
+    let time_us = time_us(&commit.time);
+    let mut events = Vec::with_capacity(wanted.len());
+
+    for op in wanted {
+        let Some((collection, rkey)) = op.path.split_once('/') else {
+            continue;
+        };
+
+        let record = match (op.action.as_str(), &op.cid) {
+            ("create" | "update", Some(cid)) => {
+                let Some(block) = blocks.get(cid) else {
+                    warn!("missing block {} for {}/{}", cid, commit.repo, op.path);
+                    continue;
+                };
+                let data = match jacquard_common::types::value::from_cbor::<
+                    jacquard_common::types::value::Data,
+                >(block)
+                {
+                    Ok(data) => data,
+                    Err(e) => {
+                        warn!("invalid record cbor for {}/{}: {}", commit.repo, op.path, e);
+                        continue;
+                    }
+                };
+                Some(serde_json::to_value(&data)?)
+            }
+            ("delete", _) => None,
+            _ => {
+                warn!(
+                    "unexpected op {} for {}/{}",
+                    op.action, commit.repo, op.path
+                );
+                continue;
+            }
+        };
+
+        events.push(to_jetstream_event(json!({
+            "did": commit.repo,
+            "time_us": time_us,
+            "kind": "commit",
+            "commit": {
+                "rev": commit.rev,
+                "operation": op.action,
+                "collection": collection,
+                "rkey": rkey,
+                "record": record,
+                "cid": op.cid.map(|c| c.to_string()),
+            },
+        }))?);
+    }
+
+    Ok(DecodedFrame {
+        seq: commit.seq,
+        events,
+    })
+}
+
+// building events from their jetstream wire form keeps the ingestors oblivious
+// to which source a record came from
+fn to_jetstream_event(value: Value) -> Result<JetstreamEvent<Value>> {
+    serde_json::from_value(value).context("failed to build jetstream event")
+}
+
+// jetstream events need a time; a frame with an unreadable one is stamped
+// with when we received it
+fn time_us(time: &str) -> i64 {
+    chrono::DateTime::parse_from_rfc3339(time)
+        .map(|dt| dt.timestamp_micros())
+        .unwrap_or_else(|_| chrono::Utc::now().timestamp_micros())
+}
+
+/// Read every block from a commit's CAR slice, keyed by CID. Each block must
+/// hash to its CID, and the slice must be rooted at `repo`'s commit at `rev`.
+/// The relay has already checked the commit signature.
+fn read_car_blocks(repo: &str, rev: &str, car: &[u8]) -> Result<HashMap<Cid, Vec<u8>>> {
+    let mut verifier = CarVerifier::new().keep_blocks();
+    verifier.update(car)?;
+    let commit = verifier.finish(repo, None)?;
+    if commit.rev != rev {
+        anyhow::bail!("commit block is at rev {}, frame says {}", commit.rev, rev);
+    }
+
+    Ok(verifier.take_blocks())
+}
+
+/// Load the persisted firehose cursor, if any
+pub async fn load_cursor(pool: &PgPool) -> Result<Option<u64>> {
+    let cursor = sqlx::query_scalar!("SELECT cursor_value FROM firehose_cursor WHERE id = 1")
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(cursor.map(|c| c as u64))
+}
+
+/// Persist the firehose cursor
+pub async fn save_cursor(pool: &PgPool, cursor: u64) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO firehose_cursor (id, cursor_value)
+        VALUES (1, $1)
+        ON CONFLICT (id) DO UPDATE SET
+            cursor_value = EXCLUDED.cursor_value,
+            updated_at = NOW()
+        "#,
+        cursor as i64,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
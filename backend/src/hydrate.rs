@@ -0,0 +1,294 @@
+//! Turns index rows into lexicon views. Every XRPC handler builds its views
+//! here, so authors get the same handle, display name and avatar on every
+//! endpoint, and records are returned as they were written.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use jacquard_common::{
+    types::{
+        aturi::AtUri,
+        cid::Cid,
+        did::Did,
+        handle::Handle,
+        string::{Datetime, Uri},
+    },
+    CowStr, Data,
+};
+use lex_rs::co_aktivi::{
+    actor::{self, ProfileView, ProfileViewBasic},
+    event::{self, EventView, EventViewBasic, EventViewDetailed},
+};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+
+use crate::{blob::BlobKind, profile::ProfileRecord, revisions, AppState};
+
+/// The columns of `events` a view is built from
+pub struct EventRow {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub record: Value,
+    pub indexed_at: DateTime<Utc>,
+}
+
+/// An rsvp joined with the event it points at
+pub struct RsvpRow {
+    pub uri: String,
+    pub cid: String,
+    pub did: String,
+    pub status: String,
+    pub record: Value,
+    pub indexed_at: DateTime<Utc>,
+    pub subject_uri: String,
+    pub subject_cid: String,
+    /// The event's current cid, to tell whether the rsvp is stale
+    pub event_cid: String,
+    pub event_name: String,
+    pub event_starts_at: Option<DateTime<Utc>>,
+}
+
+struct Profile {
+    record: ProfileRecord,
+    /// Set for accounts with a `co.aktivi.actor.profile` record
+    indexed_at: Option<DateTime<Utc>>,
+}
+
+/// Profiles for the accounts on one page of results. Accounts with an aktivi
+/// profile use it; the rest fall back to the copy of their Bluesky profile in
+/// `bsky_profiles`. Handles come from identity events when we've seen one,
+/// otherwise from the Bluesky profile. Nothing here goes over the network.
+pub struct Profiles {
+    profiles: HashMap<String, Profile>,
+}
+
+impl Profiles {
+    pub async fn load<'a>(
+        state: &AppState,
+        dids: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self> {
+        let mut seen = HashSet::new();
+        let dids: Vec<String> = dids
+            .into_iter()
+            .filter(|did| seen.insert(*did))
+            .map(str::to_string)
+            .collect();
+        if dids.is_empty() {
+            return Ok(Self {
+                profiles: HashMap::new(),
+            });
+        }
+
+        // an aktivi profile is used as a whole, even where it leaves fields
+        // empty; the Bluesky one stands in for accounts that never created one
+        let rows = sqlx::query!(
+            r#"
+            SELECT d.did AS "did!",
+                   CASE WHEN p.did IS NULL THEN b.display_name ELSE p.display_name END AS display_name,
+                   CASE WHEN p.did IS NULL THEN b.description ELSE p.description END AS description,
+                   CASE WHEN p.did IS NULL THEN b.avatar_cid ELSE p.avatar_cid END AS avatar_cid,
+                   CASE WHEN p.did IS NULL THEN b.banner_cid ELSE p.banner_cid END AS banner_cid,
+                   p.indexed_at AS "indexed_at?",
+                   COALESCE(i.handle, b.handle) AS handle
+            FROM unnest($1::text[]) AS d(did)
+            LEFT JOIN profiles p ON p.did = d.did
+            LEFT JOIN bsky_profiles b ON b.did = d.did
+            LEFT JOIN identities i ON i.did = d.did
+            "#,
+            &dids
+        )
+        .fetch_all(&state.pool)
+        .await?;
+
+        let mut profiles = HashMap::with_capacity(rows.len());
+        for row in rows {
+            let record = ProfileRecord {
+                avatar: state.blob_urls.url_opt(
+                    &row.did,
+                    row.avatar_cid.as_deref(),
+                    BlobKind::Avatar,
+                ),
+                banner: state.blob_urls.url_opt(
+                    &row.did,
+                    row.banner_cid.as_deref(),
+                    BlobKind::Banner,
+                ),
+                did: row.did.clone(),
+                handle: row.handle,
+                display_name: row.display_name,
+                description: row.description,
+            };
+            profiles.insert(
+                row.did,
+                Profile {
+                    record,
+                    indexed_at: row.indexed_at,
+                },
+            );
+        }
+
+        Ok(Self { profiles })
+    }
+
+    /// Whether `did` has an aktivi profile
+    pub fn is_indexed(&self, did: &str) -> bool {
+        self.profiles
+            .get(did)
+            .is_some_and(|p| p.indexed_at.is_some())
+    }
+
+    pub fn basic(&self, did: &str) -> ProfileViewBasic<'static> {
+        let record = self.profiles.get(did).map(|p| &p.record);
+        ProfileViewBasic {
+            did: Did::new_owned(did).unwrap(),
+            handle: record
+                .and_then(|r| r.handle.as_ref())
+                .and_then(|h| Handle::new_owned(h).ok()),
+            display_name: record
+                .and_then(|r| r.display_name.as_ref())
+                .map(|n| CowStr::copy_from_str(n)),
+            avatar: record
+                .and_then(|r| r.avatar.as_ref())
+                .and_then(|a| Uri::new_owned(a).ok()),
+            extra_data: None,
+        }
+    }
+
+    /// The full profile view; `rsvp_count` is left for the caller to fill in
+    pub fn detailed(&self, did: &str) -> ProfileView<'static> {
+        let profile = self.profiles.get(did);
+        let record = profile.map(|p| &p.record);
+        ProfileView {
+            did: Did::new_owned(did).unwrap(),
+            handle: record
+                .and_then(|r| r.handle.as_ref())
+                .and_then(|h| Handle::new_owned(h).ok()),
+            display_name: record
+                .and_then(|r| r.display_name.as_ref())
+                .map(|n| CowStr::copy_from_str(n)),
+            description: record
+                .and_then(|r| r.description.as_ref())
+                .map(|d| CowStr::copy_from_str(d)),
+            avatar: record
+                .and_then(|r| r.avatar.as_ref())
+                .and_then(|a| Uri::new_owned(a).ok()),
+            banner: record
+                .and_then(|r| r.banner.as_ref())
+                .and_then(|b| Uri::new_owned(b).ok()),
+            rsvp_count: None,
+            indexed_at: profile
+                .and_then(|p| p.indexed_at)
+                .map(|at| Datetime::new(at.fixed_offset())),
+            extra_data: None,
+        }
+    }
+
+    pub fn event(&self, row: EventRow) -> Result<EventView<'static>> {
+        Ok(EventView {
+            uri: AtUri::new_owned(&row.uri).unwrap(),
+            cid: Cid::cow_str(CowStr::copy_from_str(&row.cid)),
+            author: self.basic(&row.did),
+            record: record_data(row.record, &row.uri)?,
+            indexed_at: Datetime::new(row.indexed_at.fixed_offset()),
+            extra_data: None,
+        })
+    }
+
+    pub fn event_detailed(
+        &self,
+        row: EventRow,
+        rsvp_count: i64,
+    ) -> Result<EventViewDetailed<'static>> {
+        Ok(EventViewDetailed {
+            uri: AtUri::new_owned(&row.uri).unwrap(),
+            cid: Cid::cow_str(CowStr::copy_from_str(&row.cid)),
+            author: self.detailed(&row.did),
+            record: record_data(row.record, &row.uri)?,
+            rsvp_count: Some(rsvp_count),
+            indexed_at: Datetime::new(row.indexed_at.fixed_offset()),
+            extra_data: None,
+        })
+    }
+}
+
+/// Views of a page of events, with their hosts
+pub async fn events(state: &AppState, rows: Vec<EventRow>) -> Result<Vec<EventView<'static>>> {
+    let profiles = Profiles::load(state, rows.iter().map(|r| r.did.as_str())).await?;
+    rows.into_iter().map(|row| profiles.event(row)).collect()
+}
+
+/// The rsvps to an event, with their authors
+pub async fn event_rsvps(
+    state: &AppState,
+    rows: Vec<RsvpRow>,
+) -> Result<Vec<event::get_rsv_ps::RsvpView<'static>>> {
+    let profiles = Profiles::load(state, rows.iter().map(|r| r.did.as_str())).await?;
+    // several rsvps can point at the same old revision, so changes are
+    // cloned out rather than taken
+    let changes = stale_changes(state, &rows).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|rsvp| event::get_rsv_ps::RsvpView {
+            uri: AtUri::new_owned(&rsvp.uri).unwrap(),
+            cid: Cid::cow_str(CowStr::copy_from_str(&rsvp.cid)),
+            author: profiles.basic(&rsvp.did),
+            status: CowStr::copy_from_str(&rsvp.status),
+            subject_stale: Some(rsvp.subject_cid != rsvp.event_cid),
+            subject_changes: changes.get(&(rsvp.subject_uri, rsvp.subject_cid)).cloned(),
+            indexed_at: Datetime::new(rsvp.indexed_at.fixed_offset()),
+            extra_data: None,
+        })
+        .collect())
+}
+
+/// An actor's rsvps, with the events they point at
+pub async fn actor_rsvps(
+    state: &AppState,
+    rows: Vec<RsvpRow>,
+) -> Result<Vec<actor::get_rsv_ps::RsvpView<'static>>> {
+    let profiles = Profiles::load(state, rows.iter().map(|r| r.did.as_str())).await?;
+    let changes = stale_changes(state, &rows).await?;
+
+    rows.into_iter()
+        .map(|rsvp| {
+            Ok(actor::get_rsv_ps::RsvpView {
+                uri: AtUri::new_owned(&rsvp.uri).unwrap(),
+                cid: Cid::cow_str(CowStr::copy_from_str(&rsvp.cid)),
+                author: profiles.basic(&rsvp.did),
+                record: record_data(rsvp.record, &rsvp.uri)?,
+                event: EventViewBasic {
+                    uri: AtUri::new_owned(&rsvp.subject_uri).unwrap(),
+                    cid: Cid::cow_str(CowStr::copy_from_str(&rsvp.subject_cid)),
+                    name: CowStr::copy_from_str(&rsvp.event_name),
+                    starts_at: rsvp
+                        .event_starts_at
+                        .map(|at| Datetime::new(at.fixed_offset())),
+                    extra_data: None,
+                },
+                subject_stale: Some(rsvp.subject_cid != rsvp.event_cid),
+                subject_changes: changes.get(&(rsvp.subject_uri, rsvp.subject_cid)).cloned(),
+                indexed_at: Datetime::new(rsvp.indexed_at.fixed_offset()),
+                extra_data: None,
+            })
+        })
+        .collect()
+}
+
+/// What changed in the events rsvps made against an older revision point at
+async fn stale_changes(
+    state: &AppState,
+    rows: &[RsvpRow],
+) -> Result<HashMap<(String, String), Vec<event::FieldChange<'static>>>> {
+    let stale: Vec<(String, String)> = rows
+        .iter()
+        .filter(|r| r.subject_cid != r.event_cid)
+        .map(|r| (r.subject_uri.clone(), r.subject_cid.clone()))
+        .collect();
+    revisions::changes_since(&state.pool, &stale).await
+}
+
+/// A stored record as lexicon data, exactly as it was written
+pub fn record_data(record: Value, uri: &str) -> Result<Data<'static>> {
+    Data::from_json_owned(record).with_context(|| format!("stored record {} is not valid", uri))
+}
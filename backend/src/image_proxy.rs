@@ -0,0 +1,366 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, ImageReader, Limits};
+use ipld_core::cid::Cid;
+use moka::{future::Cache, notification::RemovalCause};
+use std::{
+    io::Cursor,
+    path::{Path as FsPath, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Semaphore;
+use tracing::{debug, warn};
+
+use crate::{blob::PdsResolver, verify::verify_block, AppState};
+
+/// Largest blob we'll download; the lexicons cap images well below this
+const MAX_BLOB_BYTES: usize = 10 * 1024 * 1024;
+
+/// Largest width or height we'll decode. A small, well-compressed blob can
+/// still claim huge dimensions
+const MAX_DECODE_DIMENSION: u32 = 8000;
+
+/// Most memory a single decode may allocate
+const MAX_DECODE_ALLOC: u64 = 256 * 1024 * 1024;
+
+/// Decodes allowed to run at once; the rest wait for a turn
+const MAX_CONCURRENT_DECODES: usize = 4;
+
+const JPEG_QUALITY: u8 = 85;
+
+/// Default on-disk cache budget when `IMAGE_CACHE_MAX_BYTES` isn't set
+const DEFAULT_CACHE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// A size variant served by the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Thumbnail,
+    Card,
+    Full,
+}
+
+impl Preset {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "thumbnail" => Some(Preset::Thumbnail),
+            "card" => Some(Preset::Card),
+            "full" => Some(Preset::Full),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Preset::Thumbnail => "thumbnail",
+            Preset::Card => "card",
+            Preset::Full => "full",
+        }
+    }
+
+    /// Longest side of the output; smaller images aren't scaled up
+    pub fn max_dimension(&self) -> u32 {
+        match self {
+            Preset::Thumbnail => 256,
+            Preset::Card => 800,
+            Preset::Full => 2000,
+        }
+    }
+}
+
+/// Fetches image blobs from their owner's PDS, checks them against their CID
+/// and serves resized JPEG variants, cached on disk up to a size budget.
+pub struct ImageProxy {
+    http: reqwest::Client,
+    pds: PdsResolver,
+    cache_dir: PathBuf,
+    /// Cached variant files and their sizes. Evicting an entry deletes the file.
+    cache: Cache<PathBuf, u64>,
+    decodes: Semaphore,
+}
+
+impl ImageProxy {
+    pub async fn new(cache_dir: PathBuf, max_cache_bytes: u64, pds: PdsResolver) -> Result<Self> {
+        tokio::fs::create_dir_all(&cache_dir)
+            .await
+            .with_context(|| format!("failed to create {}", cache_dir.display()))?;
+
+        let cache = Cache::builder()
+            .max_capacity(max_cache_bytes)
+            .weigher(|_, size: &u64| u32::try_from(*size).unwrap_or(u32::MAX))
+            .eviction_listener(|path: Arc<PathBuf>, _, cause| {
+                if cause == RemovalCause::Replaced {
+                    return;
+                }
+                if let Err(e) = std::fs::remove_file(&*path) {
+                    if e.kind() != std::io::ErrorKind::NotFound {
+                        warn!("failed to remove cached image {}: {}", path.display(), e);
+                    }
+                }
+            })
+            .build();
+
+        // pick up the variants a previous run left on disk
+        let dir = cache_dir.clone();
+        let existing = tokio::task::spawn_blocking(move || cached_files(&dir)).await??;
+        for (path, size) in existing {
+            cache.insert(path, size).await;
+        }
+
+        Ok(Self {
+            http: reqwest::Client::builder()
+                .user_agent("aktivi/0.1.0")
+                .build()?,
+            pds,
+            cache_dir,
+            cache,
+            decodes: Semaphore::new(MAX_CONCURRENT_DECODES),
+        })
+    }
+
+    /// Configure from `IMAGE_CACHE_DIR` and `IMAGE_CACHE_MAX_BYTES`
+    pub async fn from_env(pds: PdsResolver) -> Result<Self> {
+        let cache_dir = std::env::var("IMAGE_CACHE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| std::env::temp_dir().join("aktivi-images"));
+        let max_cache_bytes = std::env::var("IMAGE_CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CACHE_BYTES);
+
+        Self::new(cache_dir, max_cache_bytes, pds).await
+    }
+
+    /// The `preset` variant of blob `cid` from `did`'s repo, as JPEG
+    pub async fn get(&self, did: &str, cid: &str, preset: Preset) -> Result<Vec<u8>> {
+        // both end up in the cache path
+        anyhow::ensure!(
+            did.starts_with("did:")
+                && did
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || ":._%-".contains(c)),
+            "invalid did: {}",
+            did
+        );
+        let cid = Cid::try_from(cid).with_context(|| format!("invalid cid: {}", cid))?;
+        let path = self.variant_path(did, &cid, preset);
+
+        for _ in 0..2 {
+            // concurrent requests for a variant that isn't cached yet share a
+            // single fetch and render
+            let mut rendered = None;
+            self.cache
+                .try_get_with(path.clone(), async {
+                    let image = self.render(did, &cid, preset).await?;
+                    self.store(&path, &image).await?;
+                    let size = image.len() as u64;
+                    rendered = Some(image);
+                    Ok::<_, anyhow::Error>(size)
+                })
+                .await
+                .map_err(|e| anyhow::anyhow!("{:#}", e))?;
+            if let Some(image) = rendered {
+                return Ok(image);
+            }
+
+            match tokio::fs::read(&path).await {
+                Ok(image) => return Ok(image),
+                // removed behind our back; render it again
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    self.cache.invalidate(&path).await;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        anyhow::bail!("cached image {} keeps disappearing", path.display())
+    }
+
+    /// Run pending cache maintenance, so evictions have deleted their files
+    pub async fn sync(&self) {
+        self.cache.run_pending_tasks().await;
+    }
+
+    fn variant_path(&self, did: &str, cid: &Cid, preset: Preset) -> PathBuf {
+        self.cache_dir
+            .join(preset.as_str())
+            .join(did.replace(':', "_"))
+            .join(format!("{}.jpg", cid))
+    }
+
+    /// Fetch a blob and render its `preset` variant
+    async fn render(&self, did: &str, cid: &Cid, preset: Preset) -> Result<Vec<u8>> {
+        let blob = self.fetch_blob(did, cid).await?;
+
+        let _permit = self.decodes.acquire().await?;
+        let image = tokio::task::spawn_blocking(move || resize(&blob, preset)).await??;
+        debug!(
+            "rendered {} {} for {} ({} bytes)",
+            preset.as_str(),
+            cid,
+            did,
+            image.len()
+        );
+
+        Ok(image)
+    }
+
+    /// Write a variant file. The cache entry is added by the caller.
+    async fn store(&self, path: &FsPath, image: &[u8]) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // write then rename, so a concurrent reader never sees half a file
+        let tmp = path.with_extension(format!("jpg.{}.tmp", rand::random::<u32>()));
+        tokio::fs::write(&tmp, image).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    async fn fetch_blob(&self, did: &str, cid: &Cid) -> Result<Vec<u8>> {
+        let pds = self.pds.resolve(did).await?;
+        let mut response = self
+            .http
+            .get(format!(
+                "{}/xrpc/com.atproto.sync.getBlob",
+                pds.trim_end_matches('/')
+            ))
+            .query(&[("did", did), ("cid", &cid.to_string())])
+            .send()
+            .await?
+            .error_for_status()
+            .with_context(|| format!("failed to fetch blob {} from {}", cid, pds))?;
+
+        let mut blob = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            blob.extend_from_slice(&chunk);
+            if blob.len() > MAX_BLOB_BYTES {
+                anyhow::bail!("blob {} is larger than {} bytes", cid, MAX_BLOB_BYTES);
+            }
+        }
+
+        verify_block(cid, &blob)?;
+        Ok(blob)
+    }
+}
+
+/// Decode an image and re-encode it as JPEG, scaled down to fit `preset`
+fn resize(blob: &[u8], preset: Preset) -> Result<Vec<u8>> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DECODE_DIMENSION);
+    limits.max_image_height = Some(MAX_DECODE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::new(Cursor::new(blob)).with_guessed_format()?;
+    reader.limits(limits);
+    let image = reader.decode().context("blob is not a supported image")?;
+
+    let max = preset.max_dimension();
+    let image = if image.width() > max || image.height() > max {
+        image.resize(max, max, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let mut out = Vec::new();
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY))?;
+    Ok(out)
+}
+
+/// Every finished variant file under `dir`, with its size
+fn cached_files(dir: &FsPath) -> Result<Vec<(PathBuf, u64)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            let path = entry.path();
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "jpg") {
+                files.push((path, metadata.len()));
+            } else if is_partial_write(&path) {
+                // leftover from an interrupted write
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Whether `path` is one of our own `{cid}.jpg.{n}.tmp` files from before the
+/// rename into place. Anything else in the cache dir isn't ours to delete
+fn is_partial_write(path: &FsPath) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".tmp"))
+        .and_then(|name| name.rsplit_once('.'))
+        .is_some_and(|(stem, n)| stem.ends_with(".jpg") && n.parse::<u32>().is_ok())
+}
+
+/// `GET /img/{preset}/{did}/{cid}`
+pub async fn handle(
+    State(state): State<Arc<AppState>>,
+    Path((preset, did, cid)): Path<(String, String, String)>,
+) -> Result<Response, StatusCode> {
+    let preset = Preset::from_name(&preset).ok_or(StatusCode::NOT_FOUND)?;
+    if !crate::handle::is_did(&did) || Cid::try_from(cid.as_str()).is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // only proxy images for accounts we index, so this isn't an open proxy
+    let known = sqlx::query_scalar!(
+        r#"
+        SELECT (
+            EXISTS (SELECT 1 FROM profiles WHERE did = $1)
+            OR EXISTS (SELECT 1 FROM events WHERE did = $1)
+            OR EXISTS (SELECT 1 FROM rsvps WHERE did = $1)
+        ) AND NOT EXISTS (SELECT 1 FROM accounts a WHERE a.did = $1 AND a.status <> 'active')
+        AS "known!"
+        "#,
+        did
+    )
+    .fetch_one(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !known {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let image = state
+        .image_proxy
+        .get(&did, &cid, preset)
+        .await
+        .map_err(|e| {
+            warn!("failed to proxy image {} for {}: {:#}", cid, did, e);
+            StatusCode::BAD_GATEWAY
+        })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/jpeg"),
+            // variants are addressed by content hash, so they never change
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+        ],
+        image,
+    )
+        .into_response())
+}
+
+#[test]
+fn test_is_partial_write() {
+    assert!(is_partial_write(FsPath::new(
+        "avatar/bafyreiabc.jpg.12345.tmp"
+    )));
+    assert!(!is_partial_write(FsPath::new("avatar/bafyreiabc.jpg")));
+    assert!(!is_partial_write(FsPath::new("avatar/notes.tmp")));
+    assert!(!is_partial_write(FsPath::new(
+        "avatar/bafyreiabc.jpg.x.tmp"
+    )));
+    assert!(!is_partial_write(FsPath::new(".gitkeep")));
+}
@@ -1,217 +1,123 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use jacquard::types::value;
-use lex_rs::community_lexicon::calendar::{event::Event, rsvp::Rsvp};
-
-use rocketman::{ingestion::LexiconIngestor, types::event::Event as JetstreamEvent};
+use rocketman::{
+    ingestion::LexiconIngestor,
+    types::event::{Event as JetstreamEvent, Operation},
+};
 use serde_json::Value;
 use sqlx::PgPool;
+use std::time::Duration;
 use tracing::{debug, info, warn};
 
-/// Ingests calendar events into the database
-pub struct EventIngestor {
+use crate::{
+    backfill_jobs::AutoBackfill,
+    sink::{
+        Applied, RawRecord, RecordKind, RecordOp, RecordSink, BSKY_PROFILE_COLLECTION,
+        RSVP_COLLECTION,
+    },
+};
+
+/// Ingests record commits (events, rsvps and profiles) through the record sink
+pub struct RecordIngestor {
     pool: PgPool,
+    sink: RecordSink,
+    backfill: AutoBackfill,
 }
 
-impl EventIngestor {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
+impl RecordIngestor {
+    pub fn new(pool: PgPool, backfill: AutoBackfill) -> Self {
+        Self {
+            sink: RecordSink::new(pool.clone()),
+            pool,
+            backfill,
+        }
     }
 }
 
 #[async_trait]
-impl LexiconIngestor for EventIngestor {
+impl LexiconIngestor for RecordIngestor {
     async fn ingest(&self, message: JetstreamEvent<Value>) -> Result<()> {
         let Some(commit) = message.commit else {
             return Ok(());
         };
 
-        let Some(record) = commit.record else {
+        // every Bluesky account's profile changes come through here; only
+        // keep the ones of accounts we show
+        if commit.collection == BSKY_PROFILE_COLLECTION
+            && !is_tracked(&self.pool, &message.did).await?
+        {
             return Ok(());
-        };
-
-        let event = value::from_json_value::<Event>(record)?;
-        let uri = format!("at://{}/{}/{}", message.did, commit.collection, commit.rkey);
-        let cid = commit.cid.as_deref().unwrap_or("unknown");
-
-        debug!("ingesting event: {}", uri);
-
-        let created_at = event.created_at.as_ref();
-        let starts_at = event.starts_at.as_ref().map(|dt| dt.as_ref());
-        let ends_at = event.ends_at.as_ref().map(|dt| dt.as_ref());
-
-        // serialize locations and uris to json
-        let locations = event
-            .locations
-            .as_ref()
-            .map(|locs| serde_json::to_value(locs))
-            .transpose()?;
-        let uris = event
-            .uris
-            .as_ref()
-            .map(|uris| serde_json::to_value(uris))
-            .transpose()?;
-
-        sqlx::query!(
-            r#"
-            INSERT INTO events (uri, cid, did, rkey, name, description, created_at, starts_at, ends_at, mode, status, locations, uris)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
-            ON CONFLICT (uri) DO UPDATE SET
-                cid = EXCLUDED.cid,
-                name = EXCLUDED.name,
-                description = EXCLUDED.description,
-                created_at = EXCLUDED.created_at,
-                starts_at = EXCLUDED.starts_at,
-                ends_at = EXCLUDED.ends_at,
-                mode = EXCLUDED.mode,
-                status = EXCLUDED.status,
-                locations = EXCLUDED.locations,
-                uris = EXCLUDED.uris
-            "#,
-            uri,
-            cid,
-            &message.did,
-            commit.rkey,
-            event.name.as_ref(),
-            event.description.as_ref().map(|d| d.as_ref()),
-            created_at,
-            starts_at,
-            ends_at,
-            event.mode.as_ref().map(|m| m.as_ref()),
-            event.status.as_ref().map(|s| s.as_ref()),
-            locations,
-            uris,
-        )
-        .execute(&self.pool)
-        .await?;
-
-        info!("ingested event: {}", uri);
-        Ok(())
-    }
-}
-
-/// Ingests calendar RSVPs into the database
-pub struct RsvpIngestor {
-    pool: PgPool,
-}
-
-impl RsvpIngestor {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
-    }
-}
+        }
 
-#[async_trait]
-impl LexiconIngestor for RsvpIngestor {
-    async fn ingest(&self, message: JetstreamEvent<Value>) -> Result<()> {
-        let Some(commit) = message.commit else {
-            return Ok(());
+        let record = match commit.operation {
+            Operation::Delete => None,
+            _ => match commit.record {
+                Some(record) => Some(RawRecord::Json(record)),
+                None => return Ok(()),
+            },
         };
 
-        let Some(record) = commit.record else {
-            return Ok(());
+        // the event an rsvp points at may belong to an account we've never seen
+        let subject_did = match &record {
+            Some(RawRecord::Json(record)) if commit.collection == RSVP_COLLECTION => record
+                .pointer("/subject/uri")
+                .and_then(|uri| uri.as_str())
+                .and_then(|uri| uri.strip_prefix("at://"))
+                .and_then(|uri| uri.split('/').next())
+                .map(str::to_string),
+            _ => None,
         };
 
-        let rsvp = value::from_json_value::<Rsvp>(record)?;
-        let uri = format!("at://{}/{}/{}", message.did, commit.collection, commit.rkey);
-        let cid = commit.cid.as_deref().unwrap_or("unknown");
-
-        debug!("ingesting rsvp: {}", uri);
-
-        // Extract uri and cid from the strongRef Data
-        let (subject_uri, subject_cid) = match &rsvp.subject {
-            value::Data::Object(obj) => {
-                let uri = obj.get("uri").and_then(|v| match v {
-                    value::Data::String(s) => Some(s.as_ref()),
-                    _ => None,
-                });
-                let cid = obj.get("cid").and_then(|v| match v {
-                    value::Data::String(s) => Some(s.as_ref()),
-                    _ => None,
-                });
-                (uri, cid)
-            }
-            _ => (None, None),
+        let op = RecordOp {
+            did: &message.did,
+            collection: &commit.collection,
+            rkey: &commit.rkey,
+            cid: commit.cid.as_deref(),
+            rev: Some(&commit.rev),
+            record,
         };
+        let uri = op.uri();
+
+        match self.sink.apply(op).await? {
+            Applied::Upserted(RecordKind::BskyProfile) => debug!("ingested {}", uri),
+            Applied::Upserted(_) => {
+                info!("ingested {}", uri);
+                self.backfill.request(&message.did).await;
+                if let Some(subject_did) = subject_did {
+                    self.backfill.request(&subject_did).await;
+                }
+            }
+            Applied::Deleted(_) => info!("deleted {}", uri),
+            Applied::Quarantined(_) => warn!("quarantined {}", uri),
+            Applied::Outdated(_) | Applied::Ignored => {}
+        }
 
-        sqlx::query!(
-            r#"
-            INSERT INTO rsvps (uri, cid, did, rkey, subject_uri, subject_cid, status)
-            VALUES ($1, $2, $3, $4, $5, $6, $7)
-            ON CONFLICT (uri) DO UPDATE SET
-                cid = EXCLUDED.cid,
-                subject_uri = EXCLUDED.subject_uri,
-                subject_cid = EXCLUDED.subject_cid,
-                status = EXCLUDED.status
-            "#,
-            uri,
-            cid,
-            &message.did,
-            commit.rkey,
-            subject_uri,
-            subject_cid,
-            rsvp.status.as_ref(),
-        )
-        .execute(&self.pool)
-        .await?;
-
-        info!("ingested rsvp: {}", uri);
         Ok(())
     }
 }
 
-/// Ingests actor profiles into the database
-pub struct ProfileIngestor {
-    pool: PgPool,
-}
-
-impl ProfileIngestor {
-    pub fn new(pool: PgPool) -> Self {
-        Self { pool }
-    }
-}
-
-#[async_trait]
-impl LexiconIngestor for ProfileIngestor {
-    async fn ingest(&self, message: JetstreamEvent<Value>) -> Result<()> {
-        let Some(commit) = message.commit else {
-            return Ok(());
-        };
-
-        let Some(record) = commit.record else {
-            return Ok(());
-        };
-
-        let profile = value::from_json_value::<lex_rs::co_aktivi::actor::profile::Profile>(record)?;
-        debug!("ingesting profile: {}", message.did);
-
-        sqlx::query!(
-            r#"
-            INSERT INTO profiles (did, display_name, description, avatar, banner)
-            VALUES ($1, $2, $3, $4, $5)
-            ON CONFLICT (did) DO UPDATE SET
-                display_name = EXCLUDED.display_name,
-                description = EXCLUDED.description,
-                avatar = EXCLUDED.avatar,
-                banner = EXCLUDED.banner,
-                updated_at = NOW()
-            "#,
-            &message.did,
-            profile.display_name.as_ref().map(|n| n.as_ref()),
-            profile.description.as_ref().map(|d| d.as_ref()),
-            profile.avatar.as_ref().map(|_| "blob_ref"), // TODO: handle blob refs properly
-            profile.banner.as_ref().map(|_| "blob_ref"), // TODO: handle blob refs properly
-        )
-        .execute(&self.pool)
-        .await?;
-
-        info!("ingested profile: {}", message.did);
-        Ok(())
-    }
+/// Whether `did` hosts events, has rsvps or already has a profile stored
+async fn is_tracked(pool: &PgPool, did: &str) -> Result<bool> {
+    let tracked = sqlx::query_scalar!(
+        r#"
+        SELECT (
+            EXISTS (SELECT 1 FROM bsky_profiles WHERE did = $1)
+            OR EXISTS (SELECT 1 FROM profiles WHERE did = $1)
+            OR EXISTS (SELECT 1 FROM events WHERE did = $1)
+            OR EXISTS (SELECT 1 FROM rsvps WHERE did = $1)
+        ) AS "tracked!"
+        "#,
+        did
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(tracked)
 }
 
 /// Ingests identity events (handle changes) into the database
-/// Only updates handles for profiles we already have
+/// Only updates handles for accounts we already have a profile for, and has
+/// their Bluesky profile fetched again
 pub struct IdentityIngestor {
     pool: PgPool,
 }
@@ -229,6 +135,8 @@ impl LexiconIngestor for IdentityIngestor {
             return Ok(());
         };
 
+        crate::profile::mark_stale(&self.pool, &identity.did).await?;
+
         let Some(handle) = identity.handle else {
             return Ok(());
         };
@@ -239,12 +147,13 @@ impl LexiconIngestor for IdentityIngestor {
             return Ok(());
         }
 
-        // only update if profile already exists
+        // only track handles for accounts we show a profile for
         let result = sqlx::query!(
             r#"
             INSERT INTO identities (did, handle, seq)
             SELECT $1, $2, $3
             WHERE EXISTS (SELECT 1 FROM profiles WHERE did = $1)
+               OR EXISTS (SELECT 1 FROM bsky_profiles WHERE did = $1)
             ON CONFLICT (did) DO UPDATE SET
                 handle = EXCLUDED.handle,
                 seq = EXCLUDED.seq,
@@ -267,7 +176,7 @@ impl LexiconIngestor for IdentityIngestor {
 }
 
 /// Ingests account events (status changes) into the database
-/// Only updates accounts for profiles we already have
+/// Only tracks accounts that have events, rsvps or profiles indexed
 pub struct AccountIngestor {
     pool: PgPool,
 }
@@ -289,27 +198,44 @@ impl LexiconIngestor for AccountIngestor {
 
         let account_status = match account.status {
             Some(AccountStatus::Activated) => "active",
-            Some(AccountStatus::TakenDown) => "suspended",
+            Some(AccountStatus::TakenDown) => "takendown",
             Some(AccountStatus::Suspended) => "suspended",
             Some(AccountStatus::Deleted) => "deleted",
             Some(AccountStatus::Deactivated) => "deactivated",
             None => "active",
         };
 
-        // only update if profile already exists
+        // every inactive account is recorded, since its content may still
+        // arrive from a backfill and the read queries hide it by this row.
+        // active accounts are only tracked once we have content for them
         let result = sqlx::query!(
             r#"
-            UPDATE profiles
-            SET updated_at = NOW()
-            WHERE did = $1
+            INSERT INTO accounts (did, status, seq)
+            SELECT $1, $2, $3
+            WHERE $2 <> 'active'
+               OR EXISTS (SELECT 1 FROM profiles WHERE did = $1)
+               OR EXISTS (SELECT 1 FROM bsky_profiles WHERE did = $1)
+               OR EXISTS (SELECT 1 FROM events WHERE did = $1)
+               OR EXISTS (SELECT 1 FROM rsvps WHERE did = $1)
+            ON CONFLICT (did) DO UPDATE SET
+                status = EXCLUDED.status,
+                seq = EXCLUDED.seq,
+                status_changed_at = CASE
+                    WHEN accounts.status = EXCLUDED.status THEN accounts.status_changed_at
+                    ELSE NOW()
+                END,
+                updated_at = NOW()
+            WHERE accounts.seq < EXCLUDED.seq
             "#,
             &account.did,
+            account_status,
+            account.seq as i64,
         )
         .execute(&self.pool)
         .await?;
 
         if result.rows_affected() > 0 {
-            debug!(
+            info!(
                 "updated account status for {}: {}",
                 account.did, account_status
             );
@@ -319,6 +245,81 @@ impl LexiconIngestor for AccountIngestor {
     }
 }
 
+/// Remove all indexed records for accounts that have been deleted for longer
+/// than the grace period. Returns the number of accounts purged.
+pub async fn purge_deleted_accounts(pool: &PgPool, grace_period: Duration) -> Result<u64> {
+    let grace_secs = grace_period.as_secs() as f64;
+    let mut tx = pool.begin().await?;
+
+    let dids = sqlx::query_scalar!(
+        r#"
+        SELECT did
+        FROM accounts
+        WHERE status = 'deleted'
+          AND status_changed_at < NOW() - make_interval(secs => $1)
+        "#,
+        grace_secs
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    if dids.is_empty() {
+        return Ok(0);
+    }
+
+    sqlx::query!("DELETE FROM rsvps WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM event_revisions WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM events WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM profiles WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM quarantined_records WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM failed_records WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM record_tombstones WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM bsky_profiles WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM repo_revs WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM backfill_jobs WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    // subject uris are at://{did}/..., so the author is the third part
+    sqlx::query!(
+        "DELETE FROM subject_fetches WHERE split_part(uri, '/', 3) = ANY($1)",
+        &dids
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("DELETE FROM identities WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM accounts WHERE did = ANY($1)", &dids)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    for did in &dids {
+        info!("purged records for deleted account: {}", did);
+    }
+
+    Ok(dids.len() as u64)
+}
+
 fn is_valid_handle(handle: &str) -> bool {
     // basic handle validation: lowercase alphanumeric with dots and hyphens
     // must not start or end with dot/hyphen
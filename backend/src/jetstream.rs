@@ -6,20 +6,31 @@ use rocketman::{
     options::JetstreamOptions,
 };
 use sqlx::PgPool;
-use std::sync::{Arc, Mutex};
-use tracing::{error, info};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tracing::{debug, error, info, warn};
 
-use crate::ingest::{
-    AccountIngestor, EventIngestor, IdentityIngestor, ProfileIngestor, RsvpIngestor,
+use crate::{
+    backfill_jobs::AutoBackfill,
+    ingest::{AccountIngestor, IdentityIngestor, RecordIngestor},
+    sink::WANTED_COLLECTIONS,
 };
 
-const EVENT_COLLECTION: &str = "community.lexicon.calendar.event";
-const RSVP_COLLECTION: &str = "community.lexicon.calendar.rsvp";
-const PROFILE_COLLECTION: &str = "co.aktivi.actor.profile";
+/// How often the in-memory cursor is written to the database
+const CURSOR_FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How far to rewind a persisted cursor on startup, in microseconds.
+/// Replaying a few seconds twice is harmless since all writes are upserts,
+/// while starting too late would drop events.
+const CURSOR_REWIND_US: u64 = 10_000_000;
 
 pub struct JetstreamConsumer {
     endpoint: JetstreamEndpoints,
     pool: PgPool,
+    // tracks the last message we've processed, shared across reconnects
+    cursor: Arc<Mutex<Option<u64>>>,
 }
 
 impl JetstreamConsumer {
@@ -33,47 +44,35 @@ impl JetstreamConsumer {
         Self {
             endpoint: ws_url,
             pool,
+            cursor: Arc::new(Mutex::new(None)),
         }
     }
 
     pub async fn consume(&self) -> Result<()> {
         let opts = JetstreamOptions::builder()
             .ws_url(self.endpoint.clone())
-            .wanted_collections(vec![
-                EVENT_COLLECTION.to_string(),
-                RSVP_COLLECTION.to_string(),
-                PROFILE_COLLECTION.to_string(),
-            ])
+            .wanted_collections(WANTED_COLLECTIONS.iter().map(|c| c.to_string()).collect())
             .build();
 
         let jetstream = JetstreamConnection::new(opts);
-
-        // create ingestors using the new Ingestors struct
-        let mut ingestors = Ingestors::new();
-
-        ingestors.commits.insert(
-            EVENT_COLLECTION.to_string(),
-            Box::new(EventIngestor::new(self.pool.clone())),
-        );
-
-        ingestors.commits.insert(
-            RSVP_COLLECTION.to_string(),
-            Box::new(RsvpIngestor::new(self.pool.clone())),
-        );
-
-        ingestors.commits.insert(
-            PROFILE_COLLECTION.to_string(),
-            Box::new(ProfileIngestor::new(self.pool.clone())),
-        );
-
-        // register identity ingestor for handle updates
-        ingestors.identity = Some(Box::new(IdentityIngestor::new(self.pool.clone())));
-
-        // register account ingestor for account status updates
-        ingestors.account = Some(Box::new(AccountIngestor::new(self.pool.clone())));
-
-        // tracks the last message we've processed
-        let cursor: Arc<Mutex<Option<u64>>> = Arc::new(Mutex::new(None));
+        let ingestors = build_ingestors(&self.pool);
+
+        // resume from the persisted cursor if we don't have one in memory yet
+        let needs_load = self.cursor.lock().unwrap().is_none();
+        if needs_load {
+            match load_cursor(&self.pool).await {
+                Ok(Some(stored)) => {
+                    let resumed = stored.saturating_sub(CURSOR_REWIND_US);
+                    info!(
+                        "resuming jetstream from cursor {} (stored: {})",
+                        resumed, stored
+                    );
+                    *self.cursor.lock().unwrap() = Some(resumed);
+                }
+                Ok(None) => info!("no stored jetstream cursor, starting at live tail"),
+                Err(e) => warn!("failed to load jetstream cursor: {}", e),
+            }
+        }
 
         // get channels
         let msg_rx = jetstream.get_msg_rx();
@@ -82,8 +81,8 @@ impl JetstreamConsumer {
         info!("connecting to jetstream");
 
         // spawn a task to process messages from the queue
-        let c_cursor = cursor.clone();
-        tokio::spawn(async move {
+        let c_cursor = self.cursor.clone();
+        let processor = tokio::spawn(async move {
             while let Ok(message) = msg_rx.recv_async().await {
                 if let Err(e) = handler::handle_message(
                     message,
@@ -98,12 +97,93 @@ impl JetstreamConsumer {
             }
         });
 
+        // periodically persist the cursor so a crash only replays a few seconds
+        let f_cursor = self.cursor.clone();
+        let f_pool = self.pool.clone();
+        let flusher = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(CURSOR_FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                let value = *f_cursor.lock().unwrap();
+                if let Some(value) = value {
+                    if let Err(e) = save_cursor(&f_pool, value).await {
+                        warn!("failed to persist jetstream cursor: {}", e);
+                    }
+                }
+            }
+        });
+
         // connect to jetstream
-        jetstream
-            .connect(cursor.clone())
+        let result = jetstream
+            .connect(self.cursor.clone())
             .await
-            .map_err(|e| anyhow::anyhow!("jetstream connection failed: {}", e))?;
+            .map_err(|e| anyhow::anyhow!("jetstream connection failed: {}", e));
+
+        flusher.abort();
+        processor.abort();
+        self.flush_cursor().await;
 
+        result?;
         Ok(())
     }
+
+    /// Write the current in-memory cursor to the database
+    pub async fn flush_cursor(&self) {
+        let value = *self.cursor.lock().unwrap();
+        if let Some(value) = value {
+            match save_cursor(&self.pool, value).await {
+                Ok(()) => debug!("flushed jetstream cursor: {}", value),
+                Err(e) => warn!("failed to persist jetstream cursor: {}", e),
+            }
+        }
+    }
+}
+
+/// Build the ingestors shared by every live ingestion source
+pub(crate) fn build_ingestors(pool: &PgPool) -> Ingestors {
+    let mut ingestors = Ingestors::new();
+    let backfill = AutoBackfill::new(pool.clone());
+
+    // every record collection goes through the same sink
+    for collection in WANTED_COLLECTIONS {
+        ingestors.commits.insert(
+            collection.to_string(),
+            Box::new(RecordIngestor::new(pool.clone(), backfill.clone())),
+        );
+    }
+
+    // register identity ingestor for handle updates
+    ingestors.identity = Some(Box::new(IdentityIngestor::new(pool.clone())));
+
+    // register account ingestor for account status updates
+    ingestors.account = Some(Box::new(AccountIngestor::new(pool.clone())));
+
+    ingestors
+}
+
+/// Load the persisted jetstream cursor, if any
+pub async fn load_cursor(pool: &PgPool) -> Result<Option<u64>> {
+    let cursor = sqlx::query_scalar!("SELECT cursor_value FROM jetstream_cursor WHERE id = 1")
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(cursor.map(|c| c as u64))
+}
+
+/// Persist the jetstream cursor
+pub async fn save_cursor(pool: &PgPool, cursor: u64) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO jetstream_cursor (id, cursor_value)
+        VALUES (1, $1)
+        ON CONFLICT (id) DO UPDATE SET
+            cursor_value = EXCLUDED.cursor_value,
+            updated_at = NOW()
+        "#,
+        cursor as i64,
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
 }
@@ -1,17 +1,29 @@
 pub mod backfill;
+pub mod backfill_jobs;
+pub mod blob;
+pub mod failed_records;
+pub mod firehose;
 pub mod handle;
+pub mod hydrate;
+pub mod image_proxy;
 pub mod ingest;
 pub mod jetstream;
+pub mod mst;
 pub mod oatproxy;
 pub mod profile;
+pub mod quarantine;
+pub mod reindex;
+pub mod revisions;
+pub mod sink;
+pub mod subjects;
+pub mod validate;
+pub mod verify;
 pub mod xrpc;
 
-use moka::future::Cache;
-
 #[derive(Clone)]
 pub struct AppState {
     pub pool: sqlx::PgPool,
-    pub profile_cache: Cache<String, profile::ProfileRecord>,
-    pub handle_validity_cache: Cache<String, bool>,
+    pub blob_urls: blob::BlobUrls,
+    pub image_proxy: std::sync::Arc<image_proxy::ImageProxy>,
     pub token_manager: std::sync::Arc<jacquard_oatproxy::TokenManager>,
 }
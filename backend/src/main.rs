@@ -1,5 +1,16 @@
-use aktivi::{jetstream::JetstreamConsumer, oatproxy, xrpc, AppState};
-use axum::Router;
+use aktivi::{
+    backfill::SyncMode,
+    backfill_jobs,
+    blob::{BlobUrls, PdsResolver},
+    firehose::FirehoseConsumer,
+    image_proxy::{self, ImageProxy},
+    ingest,
+    jetstream::JetstreamConsumer,
+    oatproxy,
+    profile::{self, BskyProfiles},
+    sink, subjects, xrpc, AppState,
+};
+use axum::{routing::get, Router};
 use jacquard_axum::IntoRouter;
 use lex_rs::co_aktivi::{
     actor::{
@@ -8,18 +19,34 @@ use lex_rs::co_aktivi::{
         get_rsv_ps::GetRsvPsRequest as ActorGetRsvPsRequest, get_timeline::GetTimelineRequest,
     },
     event::{
-        get_event_view::GetEventViewRequest, get_events::GetEventsRequest as EventGetEventsRequest,
+        get_event_history::GetEventHistoryRequest, get_event_view::GetEventViewRequest,
+        get_events::GetEventsRequest as EventGetEventsRequest,
         get_rsv_ps::GetRsvPsRequest as EventGetRsvPsRequest,
     },
     search::get_search_results::GetSearchResultsRequest,
 };
 use miette::IntoDiagnostic;
-use moka::future::Cache;
 use sqlx::postgres::PgPoolOptions;
 use std::{sync::Arc, time::Duration};
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 use tracing::info;
 
+/// How long records from deleted accounts are kept before being purged
+const DELETED_ACCOUNT_GRACE_PERIOD: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// How long record deletes are remembered to stop older writes resurrecting them
+const TOMBSTONE_RETENTION: Duration = Duration::from_secs(7 * 24 * 3600);
+
+/// Connections kept free of backfill workers, for live ingestion, the
+/// periodic jobs and HTTP handlers
+const RESERVED_CONNECTIONS: u32 = 8;
+
+/// The live ingestion source selected by `INGEST_SOURCE`
+enum LiveConsumer {
+    Jetstream(Arc<JetstreamConsumer>),
+    Firehose(Arc<FirehoseConsumer>),
+}
+
 #[tokio::main]
 async fn main() -> miette::Result<()> {
     dotenvy::dotenv().ok();
@@ -38,9 +65,19 @@ async fn main() -> miette::Result<()> {
         std::env::var("PUBLIC_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
     let bind_addr = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0:3000".to_string());
 
+    // work through backfills queued for newly seen accounts. a few workers
+    // keep the load on PDSes and the db low; the cli can drain the queue
+    // faster with `full-backfill --resume`
+    let backfill_workers: u32 = std::env::var("AUTO_BACKFILL_WORKERS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(2);
+
+    // each backfill worker holds a connection for a whole repo's transaction,
+    // so size the pool around them
     info!("connecting to database: {}", database_url);
     let pool = PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(backfill_workers + RESERVED_CONNECTIONS)
         .connect(&database_url)
         .await
         .into_diagnostic()?;
@@ -55,37 +92,130 @@ async fn main() -> miette::Result<()> {
 
     let token_manager = Arc::new(jacquard_oatproxy::TokenManager::new(public_url.to_owned()));
 
-    // create caches with 1 hour TTL
-    let profile_cache = Cache::builder()
-        .max_capacity(10_000)
-        .time_to_live(Duration::from_secs(3600))
-        .build();
+    let blob_urls = BlobUrls::from_env(&public_url);
 
-    let handle_validity_cache = Cache::builder()
-        .max_capacity(50_000)
-        .time_to_live(Duration::from_secs(3600))
-        .build();
+    let image_proxy = Arc::new(
+        ImageProxy::from_env(PdsResolver::default())
+            .await
+            .into_diagnostic()?,
+    );
 
     let state = Arc::new(AppState {
         pool: pool.clone(),
-        profile_cache,
-        handle_validity_cache,
+        blob_urls,
+        image_proxy,
         token_manager,
     });
 
-    // spawn jetstream consumer in background
-    let jetstream_pool = pool.clone();
+    // spawn the live ingestion consumer in background
+    let ingest_source = std::env::var("INGEST_SOURCE").unwrap_or_else(|_| "jetstream".to_string());
+    let consumer = match ingest_source.as_str() {
+        "firehose" => {
+            let relay_url = std::env::var("FIREHOSE_URL")
+                .unwrap_or_else(|_| "wss://relay1.us-east.bsky.network".to_string());
+            info!("ingesting from relay firehose: {}", relay_url);
+            let consumer = Arc::new(FirehoseConsumer::new(relay_url, pool.clone()));
+            let firehose_consumer = consumer.clone();
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = firehose_consumer.consume().await {
+                        tracing::error!("firehose consumer error: {}", e);
+                        tracing::info!("reconnecting in 5 seconds...");
+                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    }
+                }
+            });
+            LiveConsumer::Firehose(consumer)
+        }
+        "jetstream" => {
+            let jetstream_url = std::env::var("JETSTREAM_URL")
+                .unwrap_or_else(|_| "wss://jetstream2.us-east.bsky.network/subscribe".to_string());
+            info!("ingesting from jetstream: {}", jetstream_url);
+            let consumer = Arc::new(JetstreamConsumer::new(jetstream_url, pool.clone()));
+            let jetstream_consumer = consumer.clone();
+            tokio::spawn(async move {
+                loop {
+                    if let Err(e) = jetstream_consumer.consume().await {
+                        tracing::error!("jetstream consumer error: {}", e);
+                        tracing::info!("reconnecting in 5 seconds...");
+                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    }
+                }
+            });
+            LiveConsumer::Jetstream(consumer)
+        }
+        other => miette::bail!(
+            "unknown INGEST_SOURCE {:?}, expected \"jetstream\" or \"firehose\"",
+            other
+        ),
+    };
+
+    for _ in 0..backfill_workers {
+        let worker_pool = pool.clone();
+        tokio::spawn(async move {
+            loop {
+                match backfill_jobs::run_worker(&worker_pool, SyncMode::Incremental).await {
+                    Ok(stats) if stats.succeeded + stats.failed > 0 => info!(
+                        "auto backfill: {} succeeded, {} failed",
+                        stats.succeeded, stats.failed
+                    ),
+                    Ok(_) => {}
+                    Err(e) => tracing::error!("auto backfill worker error: {}", e),
+                }
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+        });
+    }
+
+    // fetch events that rsvps point at but we've never indexed
+    let subjects_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            match subjects::resolve_dangling(&subjects_pool).await {
+                Ok((0, 0)) => {}
+                Ok((resolved, failed)) => {
+                    info!("rsvp subjects: {} resolved, {} failed", resolved, failed)
+                }
+                Err(e) => tracing::error!("failed to resolve rsvp subjects: {}", e),
+            }
+        }
+    });
+
+    // keep local copies of the Bluesky profiles of hosts and rsvp authors, so
+    // views never have to ask the AppView
+    let profiles_pool = pool.clone();
+    let bsky_profiles = BskyProfiles::new("https://public.api.bsky.app");
     tokio::spawn(async move {
-        let consumer = JetstreamConsumer::new(
-            "wss://jetstream2.us-east.bsky.network/subscribe".to_string(),
-            jetstream_pool.clone(),
-        );
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            match profile::refresh_due(&profiles_pool, &bsky_profiles).await {
+                Ok((0, 0)) => {}
+                Ok((refreshed, failed)) => {
+                    info!("bsky profiles: {} refreshed, {} failed", refreshed, failed)
+                }
+                Err(e) => tracing::error!("failed to refresh bsky profiles: {}", e),
+            }
+        }
+    });
 
+    // purge records from deleted accounts once their grace period has passed
+    let purge_pool = pool.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
         loop {
-            if let Err(e) = consumer.consume().await {
-                tracing::error!("jetstream consumer error: {}", e);
-                tracing::info!("reconnecting in 5 seconds...");
-                tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+            interval.tick().await;
+            match ingest::purge_deleted_accounts(&purge_pool, DELETED_ACCOUNT_GRACE_PERIOD).await {
+                Ok(0) => {}
+                Ok(n) => info!("purged {} deleted accounts", n),
+                Err(e) => tracing::error!("failed to purge deleted accounts: {}", e),
+            }
+            match sink::prune_tombstones(&purge_pool, TOMBSTONE_RETENTION).await {
+                Ok(0) => {}
+                Ok(n) => info!("pruned {} record tombstones", n),
+                Err(e) => tracing::error!("failed to prune record tombstones: {}", e),
             }
         }
     });
@@ -96,6 +226,9 @@ async fn main() -> miette::Result<()> {
         .merge(GetEventViewRequest::into_router(
             xrpc::get_event_view::handle,
         ))
+        .merge(GetEventHistoryRequest::into_router(
+            xrpc::get_event_history::handle,
+        ))
         .merge(ActorGetEventsRequest::into_router(
             xrpc::actor_get_events::handle,
         ))
@@ -113,8 +246,13 @@ async fn main() -> miette::Result<()> {
         .merge(oat)
         .layer(CorsLayer::permissive());
 
+    let image_router = Router::new()
+        .route("/img/{preset}/{did}/{cid}", get(image_proxy::handle))
+        .with_state(state.clone());
+
     let app = Router::new()
         .merge(xrpc_router)
+        .merge(image_router)
         .layer(TraceLayer::new_for_http());
 
     info!("listening on {}", bind_addr);
@@ -122,7 +260,41 @@ async fn main() -> miette::Result<()> {
     let listener = tokio::net::TcpListener::bind(bind_addr)
         .await
         .into_diagnostic()?;
-    axum::serve(listener, app).await.into_diagnostic()?;
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .into_diagnostic()?;
+
+    // persist the ingestion cursor so we resume where we left off
+    info!("shutting down, flushing ingestion cursor");
+    match &consumer {
+        LiveConsumer::Jetstream(c) => c.flush_cursor().await,
+        LiveConsumer::Firehose(c) => c.flush_cursor().await,
+    }
 
     Ok(())
 }
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install ctrl+c handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install sigterm handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use ipld_core::cid::Cid;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+struct Node {
+    l: Option<Cid>,
+    e: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    p: usize,
+    #[serde(with = "serde_bytes")]
+    k: Vec<u8>,
+    v: Cid,
+    t: Option<Cid>,
+}
+
+/// Walk whatever part of an MST is present in `blocks`, starting at `root`,
+/// and return the `(collection/rkey, record cid)` of every record whose block
+/// is also present.
+///
+/// For a `getRepo` diff (`since`), the CAR only holds nodes and records that
+/// changed after that rev, so missing subtrees are unchanged and skipped.
+pub fn changed_records(root: Cid, blocks: &HashMap<Cid, Vec<u8>>) -> Result<Vec<(String, Cid)>> {
+    let mut out = Vec::new();
+    walk(root, blocks, &mut out)?;
+    Ok(out)
+}
+
+/// Find the cid of the record stored under `key` (`collection/rkey`) by
+/// following the path to it from `root`, e.g. through the proof a
+/// `sync.getRecord` CAR carries. Returns `None` if the tree has no such key.
+/// A node missing from `blocks` on the way means the proof is incomplete.
+pub fn lookup(root: Cid, blocks: &HashMap<Cid, Vec<u8>>, key: &str) -> Result<Option<Cid>> {
+    let key = key.as_bytes();
+    let mut next = Some(root);
+
+    while let Some(node_cid) = next {
+        let block = blocks
+            .get(&node_cid)
+            .with_context(|| format!("MST node {} missing from proof", node_cid))?;
+        let node: Node = serde_ipld_dagcbor::from_slice(block)
+            .with_context(|| format!("invalid MST node {}", node_cid))?;
+
+        // the key is either in this node, or in the subtree just left of the
+        // first entry sorting after it
+        next = node.l;
+        let mut entry_key: Vec<u8> = Vec::new();
+        for entry in node.e {
+            if entry.p > entry_key.len() {
+                anyhow::bail!("invalid MST key prefix in node {}", node_cid);
+            }
+            entry_key.truncate(entry.p);
+            entry_key.extend_from_slice(&entry.k);
+
+            match key.cmp(entry_key.as_slice()) {
+                std::cmp::Ordering::Less => break,
+                std::cmp::Ordering::Equal => return Ok(Some(entry.v)),
+                std::cmp::Ordering::Greater => next = entry.t,
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn walk(node_cid: Cid, blocks: &HashMap<Cid, Vec<u8>>, out: &mut Vec<(String, Cid)>) -> Result<()> {
+    let Some(block) = blocks.get(&node_cid) else {
+        return Ok(());
+    };
+
+    let node: Node = serde_ipld_dagcbor::from_slice(block)
+        .with_context(|| format!("invalid MST node {}", node_cid))?;
+
+    if let Some(left) = node.l {
+        walk(left, blocks, out)?;
+    }
+
+    // keys are prefix-compressed against the previous entry in the node
+    let mut key: Vec<u8> = Vec::new();
+    for entry in node.e {
+        if entry.p > key.len() {
+            anyhow::bail!("invalid MST key prefix in node {}", node_cid);
+        }
+        key.truncate(entry.p);
+        key.extend_from_slice(&entry.k);
+
+        if blocks.contains_key(&entry.v) {
+            let path = String::from_utf8(key.clone()).context("MST key is not utf-8")?;
+            out.push((path, entry.v));
+        }
+
+        if let Some(tree) = entry.t {
+            walk(tree, blocks, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_changed_records_skips_missing_blocks() {
+    use ipld_core::ipld::Ipld;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    fn cid_for(data: &[u8]) -> Cid {
+        let mh = multihash::Multihash::wrap(0x12, &Sha256::digest(data)).unwrap();
+        Cid::new_v1(0x71, mh)
+    }
+
+    fn entry(p: u64, k: &str, v: Cid) -> Ipld {
+        let mut e = BTreeMap::new();
+        e.insert("p".to_string(), Ipld::Integer(p as i128));
+        e.insert("k".to_string(), Ipld::Bytes(k.as_bytes().to_vec()));
+        e.insert("v".to_string(), Ipld::Link(v));
+        e.insert("t".to_string(), Ipld::Null);
+        Ipld::Map(e)
+    }
+
+    let changed = b"changed record".to_vec();
+    let unchanged = cid_for(b"unchanged record");
+
+    let mut node = BTreeMap::new();
+    node.insert("l".to_string(), Ipld::Null);
+    node.insert(
+        "e".to_string(),
+        Ipld::List(vec![
+            entry(
+                0,
+                "community.lexicon.calendar.event/3kaaa",
+                cid_for(&changed),
+            ),
+            entry(35, "bbb", unchanged),
+        ]),
+    );
+    let node = serde_ipld_dagcbor::to_vec(&Ipld::Map(node)).unwrap();
+    let root = cid_for(&node);
+
+    let blocks = HashMap::from([(root, node), (cid_for(&changed), changed.clone())]);
+    let records = changed_records(root, &blocks).unwrap();
+
+    assert_eq!(
+        records,
+        vec![(
+            "community.lexicon.calendar.event/3kaaa".to_string(),
+            cid_for(&changed)
+        )]
+    );
+
+    // an unknown root means nothing changed that we can see
+    assert!(changed_records(unchanged, &blocks).unwrap().is_empty());
+}
+
+#[test]
+fn test_lookup_follows_proof() {
+    use ipld_core::ipld::Ipld;
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    fn cid_for(data: &[u8]) -> Cid {
+        let mh = multihash::Multihash::wrap(0x12, &Sha256::digest(data)).unwrap();
+        Cid::new_v1(0x71, mh)
+    }
+
+    fn node(l: Option<Cid>, entries: Vec<(u64, &str, Cid, Option<Cid>)>) -> Vec<u8> {
+        let entries = entries
+            .into_iter()
+            .map(|(p, k, v, t)| {
+                let mut e = BTreeMap::new();
+                e.insert("p".to_string(), Ipld::Integer(p as i128));
+                e.insert("k".to_string(), Ipld::Bytes(k.as_bytes().to_vec()));
+                e.insert("v".to_string(), Ipld::Link(v));
+                e.insert("t".to_string(), t.map_or(Ipld::Null, Ipld::Link));
+                Ipld::Map(e)
+            })
+            .collect();
+        let mut n = BTreeMap::new();
+        n.insert("l".to_string(), l.map_or(Ipld::Null, Ipld::Link));
+        n.insert("e".to_string(), Ipld::List(entries));
+        serde_ipld_dagcbor::to_vec(&Ipld::Map(n)).unwrap()
+    }
+
+    let record = cid_for(b"event");
+    let other = cid_for(b"other event");
+
+    // the record sits in the subtree left of the root's only entry
+    let leaf = node(
+        None,
+        vec![(0, "community.lexicon.calendar.event/3kaaa", record, None)],
+    );
+    let root = node(
+        Some(cid_for(&leaf)),
+        vec![(0, "community.lexicon.calendar.event/3kzzz", other, None)],
+    );
+    let root_cid = cid_for(&root);
+    let blocks = HashMap::from([(root_cid, root.clone()), (cid_for(&leaf), leaf)]);
+
+    assert_eq!(
+        lookup(root_cid, &blocks, "community.lexicon.calendar.event/3kaaa").unwrap(),
+        Some(record)
+    );
+    assert_eq!(
+        lookup(root_cid, &blocks, "community.lexicon.calendar.event/3kzzz").unwrap(),
+        Some(other)
+    );
+    assert_eq!(
+        lookup(root_cid, &blocks, "community.lexicon.calendar.event/3kzzzz").unwrap(),
+        None
+    );
+
+    // without the leaf the proof doesn't show whether the key is there
+    let partial = HashMap::from([(root_cid, root)]);
+    assert!(lookup(root_cid, &partial, "community.lexicon.calendar.event/3kaaa").is_err());
+}
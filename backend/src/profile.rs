@@ -1,6 +1,27 @@
 use anyhow::Result;
+use futures::future::join_all;
 use moka::future::Cache;
 use serde::Deserialize;
+use sqlx::PgPool;
+use std::{collections::HashMap, time::Duration};
+use tracing::warn;
+
+use crate::blob::bsky_cdn_cid;
+
+/// Most actors `app.bsky.actor.getProfiles` accepts in one call
+pub const GET_PROFILES_BATCH: usize = 25;
+
+/// Handle the Bluesky AppView reports for accounts whose handle doesn't verify
+const INVALID_HANDLE: &str = "handle.invalid";
+
+/// How many profiles a single refresh pass fetches
+const REFRESH_BATCH: i64 = 250;
+
+/// How long a stored Bluesky profile is kept before it's fetched again
+const REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 3600);
+
+/// How long to wait before asking the AppView again after a failed lookup
+const RETRY_INTERVAL: Duration = Duration::from_secs(15 * 60);
 
 #[derive(Deserialize)]
 pub struct BskyProfile {
@@ -8,10 +29,16 @@ pub struct BskyProfile {
     pub handle: String,
     #[serde(rename = "displayName")]
     pub display_name: Option<String>,
+    pub description: Option<String>,
     pub avatar: Option<String>,
     pub banner: Option<String>,
 }
 
+#[derive(Deserialize)]
+struct GetProfilesOutput {
+    profiles: Vec<BskyProfile>,
+}
+
 #[derive(Clone)]
 pub struct ProfileRecord {
     pub did: String,
@@ -22,33 +49,189 @@ pub struct ProfileRecord {
     pub banner: Option<String>,
 }
 
-pub async fn fetch_bsky_profile(
-    did: &str,
-    handle_validity_cache: &Cache<String, bool>,
-) -> Result<ProfileRecord> {
-    let url = format!(
-        "https://public.api.bsky.app/xrpc/app.bsky.actor.getProfile?actor={}",
+/// A Bluesky profile as stored in `bsky_profiles`, with images as blob cids
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchedProfile {
+    pub handle: Option<String>,
+    pub display_name: Option<String>,
+    pub description: Option<String>,
+    pub avatar_cid: Option<String>,
+    pub banner_cid: Option<String>,
+}
+
+/// Looks up Bluesky profiles through `app.bsky.actor.getProfiles`. Only the
+/// refresh worker uses it; read paths go to `bsky_profiles`.
+#[derive(Clone)]
+pub struct BskyProfiles {
+    http: reqwest::Client,
+    appview_url: String,
+    handle_validity_cache: Cache<String, bool>,
+}
+
+impl BskyProfiles {
+    pub fn new(appview_url: impl Into<String>) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .user_agent("aktivi/0.1.0")
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("failed to build http client"),
+            appview_url: appview_url.into(),
+            handle_validity_cache: Cache::builder()
+                .max_capacity(50_000)
+                .time_to_live(Duration::from_secs(3600))
+                .build(),
+        }
+    }
+
+    /// Profiles for up to [`GET_PROFILES_BATCH`] DIDs. DIDs the AppView
+    /// doesn't know are left out.
+    pub async fn fetch_batch(&self, dids: &[String]) -> Result<HashMap<String, FetchedProfile>> {
+        let actors: Vec<(&str, &str)> = dids.iter().map(|did| ("actors", did.as_str())).collect();
+        let output: GetProfilesOutput = self
+            .http
+            .get(format!(
+                "{}/xrpc/app.bsky.actor.getProfiles",
+                self.appview_url.trim_end_matches('/')
+            ))
+            .query(&actors)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let profiles = join_all(output.profiles.into_iter().map(|profile| async move {
+            let handle = if profile.handle == INVALID_HANDLE {
+                None
+            } else {
+                crate::handle::validate_with_cache(
+                    &profile.handle,
+                    &profile.did,
+                    &self.handle_validity_cache,
+                )
+                .await
+            };
+
+            // the AppView only hands out CDN URLs; keep the blob cid so views
+            // can point at our own image proxy
+            let fetched = FetchedProfile {
+                handle,
+                display_name: profile.display_name,
+                description: profile.description,
+                avatar_cid: profile.avatar.as_deref().and_then(bsky_cdn_cid),
+                banner_cid: profile.banner.as_deref().and_then(bsky_cdn_cid),
+            };
+            (profile.did, fetched)
+        }))
+        .await;
+
+        Ok(profiles.into_iter().collect())
+    }
+}
+
+/// Fetches the Bluesky profiles of event hosts and rsvp authors we haven't
+/// stored yet, or whose stored copy is due for a refresh.
+/// Returns (refreshed, failed).
+pub async fn refresh_due(pool: &PgPool, bsky: &BskyProfiles) -> Result<(usize, usize)> {
+    let due = sqlx::query_scalar!(
+        r#"
+        SELECT d.did AS "did!"
+        FROM (SELECT did FROM events UNION SELECT did FROM rsvps) d
+        LEFT JOIN bsky_profiles b ON b.did = d.did
+        WHERE (b.did IS NULL OR b.refresh_after <= NOW())
+          AND NOT EXISTS (SELECT 1 FROM accounts a WHERE a.did = d.did AND a.status <> 'active')
+        ORDER BY b.refresh_after NULLS FIRST
+        LIMIT $1
+        "#,
+        REFRESH_BATCH
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut refreshed = 0;
+    let mut failed = 0;
+    for chunk in due.chunks(GET_PROFILES_BATCH) {
+        match bsky.fetch_batch(chunk).await {
+            Ok(mut found) => {
+                for did in chunk {
+                    // accounts the AppView doesn't know are stored empty, so
+                    // they aren't asked for again until the next refresh
+                    let profile = found.remove(did).unwrap_or_default();
+                    store(pool, did, &profile).await?;
+                    refreshed += 1;
+                }
+            }
+            Err(e) => {
+                warn!("failed to fetch {} bsky profiles: {:#}", chunk.len(), e);
+                sqlx::query!(
+                    r#"
+                    INSERT INTO bsky_profiles (did, source, refresh_after)
+                    SELECT did, 'appview', NOW() + make_interval(secs => $2)
+                    FROM unnest($1::text[]) AS d(did)
+                    ON CONFLICT (did) DO UPDATE SET
+                        refresh_after = EXCLUDED.refresh_after,
+                        updated_at = NOW()
+                    "#,
+                    chunk,
+                    RETRY_INTERVAL.as_secs() as f64
+                )
+                .execute(pool)
+                .await?;
+                failed += chunk.len();
+            }
+        }
+    }
+
+    Ok((refreshed, failed))
+}
+
+/// Store a profile fetched from the AppView. Profiles indexed from the
+/// account's own `app.bsky.actor.profile` record are fresher than the
+/// AppView's copy, so only their handle is updated.
+pub async fn store(pool: &PgPool, did: &str, profile: &FetchedProfile) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO bsky_profiles (
+            did, source, handle, display_name, description, avatar_cid, banner_cid,
+            refreshed_at, refresh_after
+        )
+        VALUES ($1, 'appview', $2, $3, $4, $5, $6, NOW(), NOW() + make_interval(secs => $7))
+        ON CONFLICT (did) DO UPDATE SET
+            handle = EXCLUDED.handle,
+            display_name = CASE WHEN bsky_profiles.source = 'record'
+                THEN bsky_profiles.display_name ELSE EXCLUDED.display_name END,
+            description = CASE WHEN bsky_profiles.source = 'record'
+                THEN bsky_profiles.description ELSE EXCLUDED.description END,
+            avatar_cid = CASE WHEN bsky_profiles.source = 'record'
+                THEN bsky_profiles.avatar_cid ELSE EXCLUDED.avatar_cid END,
+            banner_cid = CASE WHEN bsky_profiles.source = 'record'
+                THEN bsky_profiles.banner_cid ELSE EXCLUDED.banner_cid END,
+            refreshed_at = EXCLUDED.refreshed_at,
+            refresh_after = EXCLUDED.refresh_after,
+            updated_at = NOW()
+        "#,
+        did,
+        profile.handle,
+        profile.display_name,
+        profile.description,
+        profile.avatar_cid,
+        profile.banner_cid,
+        REFRESH_INTERVAL.as_secs() as f64
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Have `did`'s Bluesky profile fetched again on the next refresh pass, e.g.
+/// after its handle changed
+pub async fn mark_stale(pool: &PgPool, did: &str) -> Result<()> {
+    sqlx::query!(
+        "UPDATE bsky_profiles SET refresh_after = NOW() WHERE did = $1",
         did
-    );
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .header("User-Agent", "aktivi/0.1.0")
-        .send()
-        .await?;
-
-    let bsky_profile: BskyProfile = response.json().await?;
-
-    let handle =
-        crate::handle::validate_with_cache(&bsky_profile.handle, did, handle_validity_cache).await;
-
-    Ok(ProfileRecord {
-        did: bsky_profile.did,
-        handle,
-        display_name: bsky_profile.display_name,
-        description: None,
-        avatar: bsky_profile.avatar,
-        banner: bsky_profile.banner,
-    })
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
 }
@@ -0,0 +1,138 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{PgConnection, PgPool};
+
+use crate::sink::RecordOp;
+
+/// A record kept out of the public tables because it failed validation
+#[derive(Debug)]
+pub struct QuarantinedRecord {
+    pub uri: String,
+    pub did: String,
+    pub collection: String,
+    pub rkey: String,
+    pub cid: String,
+    pub rev: Option<String>,
+    pub record: Value,
+    pub reasons: Vec<String>,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+/// Store a record that failed validation, unless the same or a newer version
+/// of it is already quarantined. With `same_rev` a version at the op's own rev
+/// is replaced. Returns whether anything was written.
+pub async fn save(
+    conn: &mut PgConnection,
+    op: &RecordOp<'_>,
+    cid: &str,
+    record: Value,
+    reasons: &[String],
+    same_rev: bool,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO quarantined_records (uri, did, collection, rkey, cid, rev, record, reasons)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (uri) DO UPDATE SET
+            cid = EXCLUDED.cid,
+            rev = EXCLUDED.rev,
+            record = EXCLUDED.record,
+            reasons = EXCLUDED.reasons,
+            quarantined_at = NOW()
+        WHERE quarantined_records.rev IS NULL OR quarantined_records.rev < EXCLUDED.rev
+           OR ($9 AND quarantined_records.rev = EXCLUDED.rev)
+        "#,
+        op.uri(),
+        op.did,
+        op.collection,
+        op.rkey,
+        cid,
+        op.rev,
+        record,
+        reasons,
+        same_rev,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Remove the quarantined version of a record if it's older than `rev` (or
+/// from `rev` itself with `same_rev`), or whatever version there is when `rev`
+/// is `None`. Returns whether one was removed.
+pub async fn clear(
+    conn: &mut PgConnection,
+    uri: &str,
+    rev: Option<&str>,
+    same_rev: bool,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM quarantined_records
+        WHERE uri = $1
+          AND ($2::text IS NULL OR rev IS NULL OR rev < $2 OR ($3 AND rev = $2))
+        "#,
+        uri,
+        rev,
+        same_rev
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether a version of the record at or after `rev` is in quarantine. With
+/// `same_rev` only newer versions count.
+pub async fn quarantined_since(
+    conn: &mut PgConnection,
+    uri: &str,
+    rev: Option<&str>,
+    same_rev: bool,
+) -> Result<bool> {
+    let Some(rev) = rev else {
+        return Ok(false);
+    };
+
+    let quarantined = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM quarantined_records
+            WHERE uri = $1 AND (rev > $2 OR (NOT $3 AND rev = $2))
+        ) AS "quarantined!"
+        "#,
+        uri,
+        rev,
+        same_rev
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(quarantined)
+}
+
+/// Most recently quarantined records, optionally only for one collection
+pub async fn list(
+    pool: &PgPool,
+    collection: Option<&str>,
+    limit: i64,
+) -> Result<Vec<QuarantinedRecord>> {
+    let rows = sqlx::query_as!(
+        QuarantinedRecord,
+        r#"
+        SELECT uri, did, collection, rkey, cid, rev, record, reasons, quarantined_at
+        FROM quarantined_records
+        WHERE ($1::text IS NULL OR collection = $1)
+        ORDER BY quarantined_at DESC
+        LIMIT $2
+        "#,
+        collection,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
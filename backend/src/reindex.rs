@@ -0,0 +1,97 @@
+use anyhow::Result;
+use sqlx::PgPool;
+use tracing::{info, warn};
+
+use crate::{
+    failed_records,
+    sink::{self, Applied, RawRecord, RecordOp},
+};
+
+/// Stored records read per page
+const PAGE_SIZE: i64 = 500;
+
+/// What a reindex run did
+#[derive(Debug, Default)]
+pub struct ReindexSummary {
+    pub reindexed: usize,
+    /// Records that no longer pass validation and were moved to quarantine
+    pub quarantined: usize,
+    /// Records the sink now rejects; they're in `failed_records`
+    pub failed: usize,
+}
+
+/// Rebuild the derived columns of every indexed record from its stored raw
+/// json, by sending it through the sink again at the rev it was written at,
+/// replacing the rows from that rev.
+/// Quarantined records are included, so a rule change can let them back in.
+/// Profiles indexed before their cid was kept are skipped until they're
+/// next synced.
+pub async fn reindex(pool: &PgPool, collection: Option<&str>) -> Result<ReindexSummary> {
+    let mut conn = pool.acquire().await?;
+    let mut summary = ReindexSummary::default();
+    let mut after = String::new();
+
+    loop {
+        let page = sqlx::query!(
+            r#"
+            SELECT uri AS "uri!", did AS "did!", collection AS "collection!", rkey AS "rkey!",
+                   cid AS "cid!", rev, record AS "record!"
+            FROM (
+                SELECT uri, did, 'community.lexicon.calendar.event' AS collection, rkey, cid, rev, record
+                FROM events
+                UNION ALL
+                SELECT uri, did, 'community.lexicon.calendar.rsvp', rkey, cid, rev, record
+                FROM rsvps
+                UNION ALL
+                SELECT 'at://' || did || '/co.aktivi.actor.profile/self', did, 'co.aktivi.actor.profile', 'self', cid, rev, record
+                FROM profiles
+                WHERE cid IS NOT NULL
+                UNION ALL
+                SELECT uri, did, collection, rkey, cid, rev, record
+                FROM quarantined_records
+            ) stored
+            WHERE ($1::text IS NULL OR collection = $1) AND uri > $2
+            ORDER BY uri
+            LIMIT $3
+            "#,
+            collection,
+            &after,
+            PAGE_SIZE
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = last.uri.clone();
+
+        for row in page {
+            let op = RecordOp {
+                did: &row.did,
+                collection: &row.collection,
+                rkey: &row.rkey,
+                cid: Some(&row.cid),
+                rev: row.rev.as_deref(),
+                record: Some(RawRecord::Json(row.record)),
+            };
+
+            match sink::reapply(&mut conn, &op).await {
+                Ok(Applied::Quarantined(_)) => summary.quarantined += 1,
+                Ok(_) => summary.reindexed += 1,
+                Err(e) => {
+                    warn!("failed to reindex {}: {:#}", op.uri(), e);
+                    failed_records::save(&mut conn, &op, &e).await?;
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        info!(
+            "reindexed {} records so far ({} quarantined, {} failed)",
+            summary.reindexed, summary.quarantined, summary.failed
+        );
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use jacquard_common::{CowStr, Data};
+use lex_rs::co_aktivi::event::FieldChange;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+/// The fields of an event we track across revisions
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSnapshot {
+    pub name: String,
+    pub description: Option<String>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+    pub mode: Option<String>,
+    pub status: Option<String>,
+    pub locations: Option<Value>,
+    pub uris: Option<Value>,
+}
+
+impl EventSnapshot {
+    // (lexicon field name, value) for every tracked field
+    fn fields(&self) -> [(&'static str, Option<Value>); 8] {
+        [
+            ("name", Some(Value::from(self.name.as_str()))),
+            ("description", self.description.as_deref().map(Value::from)),
+            (
+                "startsAt",
+                self.starts_at.map(|dt| Value::from(dt.to_rfc3339())),
+            ),
+            (
+                "endsAt",
+                self.ends_at.map(|dt| Value::from(dt.to_rfc3339())),
+            ),
+            ("mode", self.mode.as_deref().map(Value::from)),
+            ("status", self.status.as_deref().map(Value::from)),
+            ("locations", self.locations.clone()),
+            ("uris", self.uris.clone()),
+        ]
+    }
+
+    /// The tracked fields as a record-shaped json object, omitting unset ones
+    pub fn to_json(&self) -> Value {
+        Value::Object(
+            self.fields()
+                .into_iter()
+                .filter_map(|(field, value)| Some((field.to_string(), value?)))
+                .collect(),
+        )
+    }
+}
+
+/// Field-level differences going from `before` to `after`
+pub fn diff(before: &EventSnapshot, after: &EventSnapshot) -> Vec<FieldChange<'static>> {
+    before
+        .fields()
+        .into_iter()
+        .zip(after.fields())
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((field, old), (_, new))| FieldChange {
+            field: CowStr::new_static(field),
+            before: old.and_then(|v| Data::from_json_owned(v).ok()),
+            after: new.and_then(|v| Data::from_json_owned(v).ok()),
+            extra_data: None,
+        })
+        .collect()
+}
+
+/// For each `(event uri, cid)` an rsvp was made against, what has changed in
+/// the event since. Subjects whose old revision we never saw are left out.
+pub async fn changes_since(
+    pool: &PgPool,
+    subjects: &[(String, String)],
+) -> Result<HashMap<(String, String), Vec<FieldChange<'static>>>> {
+    if subjects.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let (uris, cids): (Vec<String>, Vec<String>) = subjects.iter().cloned().unzip();
+
+    // an event edited back to an earlier version has that cid more than once;
+    // the revisions are identical, so any one of them will do
+    let rows = sqlx::query!(
+        r#"
+        SELECT DISTINCT ON (er.uri, er.cid) er.uri, er.cid,
+               er.name AS old_name, er.description AS old_description,
+               er.starts_at AS old_starts_at, er.ends_at AS old_ends_at,
+               er.mode AS old_mode, er.status AS old_status,
+               er.locations AS old_locations, er.uris AS old_uris,
+               e.name, e.description, e.starts_at, e.ends_at,
+               e.mode, e.status, e.locations, e.uris
+        FROM UNNEST($1::text[], $2::text[]) AS s(uri, cid)
+        JOIN event_revisions er ON er.uri = s.uri AND er.cid = s.cid
+        JOIN events e ON e.uri = s.uri
+        "#,
+        &uris,
+        &cids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let before = EventSnapshot {
+                name: row.old_name,
+                description: row.old_description,
+                starts_at: row.old_starts_at,
+                ends_at: row.old_ends_at,
+                mode: row.old_mode,
+                status: row.old_status,
+                locations: row.old_locations,
+                uris: row.old_uris,
+            };
+            let after = EventSnapshot {
+                name: row.name,
+                description: row.description,
+                starts_at: row.starts_at,
+                ends_at: row.ends_at,
+                mode: row.mode,
+                status: row.status,
+                locations: row.locations,
+                uris: row.uris,
+            };
+            ((row.uri, row.cid), diff(&before, &after))
+        })
+        .collect())
+}
+
+#[test]
+fn test_diff() {
+    let before = EventSnapshot {
+        name: "meetup".to_string(),
+        description: None,
+        starts_at: Some("2025-06-01T18:00:00Z".parse().unwrap()),
+        ends_at: None,
+        mode: Some("community.lexicon.calendar.event#inperson".to_string()),
+        status: Some("community.lexicon.calendar.event#scheduled".to_string()),
+        locations: None,
+        uris: None,
+    };
+    assert!(diff(&before, &before).is_empty());
+
+    let after = EventSnapshot {
+        starts_at: Some("2025-06-08T18:00:00Z".parse().unwrap()),
+        status: Some("community.lexicon.calendar.event#rescheduled".to_string()),
+        description: Some("moved a week".to_string()),
+        ..before.clone()
+    };
+    let changes = diff(&before, &after);
+    let fields: Vec<&str> = changes.iter().map(|c| c.field.as_ref()).collect();
+    assert_eq!(fields, ["description", "startsAt", "status"]);
+    assert!(changes[0].before.is_none());
+    assert!(changes[0].after.is_some());
+}
@@ -0,0 +1,720 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use jacquard::types::value;
+use lex_rs::co_aktivi::actor::profile::Profile;
+use lex_rs::community_lexicon::calendar::{event::Event, rsvp::Rsvp};
+use serde_json::Value;
+use sqlx::{Connection, PgConnection, PgPool};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::{blob::BlobRef, failed_records, quarantine, validate};
+
+pub const EVENT_COLLECTION: &str = "community.lexicon.calendar.event";
+pub const RSVP_COLLECTION: &str = "community.lexicon.calendar.rsvp";
+pub const PROFILE_COLLECTION: &str = "co.aktivi.actor.profile";
+pub const BSKY_PROFILE_COLLECTION: &str = "app.bsky.actor.profile";
+
+/// Collections the sink knows how to index
+pub const WANTED_COLLECTIONS: &[&str] = &[
+    EVENT_COLLECTION,
+    RSVP_COLLECTION,
+    PROFILE_COLLECTION,
+    BSKY_PROFILE_COLLECTION,
+];
+
+/// A record body in whichever encoding the source delivered it
+pub enum RawRecord<'a> {
+    /// JSON from Jetstream or a converted firehose op
+    Json(Value),
+    /// DAG-CBOR block from a CAR file
+    Cbor(&'a [u8]),
+}
+
+impl RawRecord<'_> {
+    /// The record as json. CBOR is converted to the atproto json form, so
+    /// links and bytes come out as `$link` and `$bytes` objects.
+    pub fn to_json(&self) -> Result<Value> {
+        match self {
+            RawRecord::Json(json) => Ok(json.clone()),
+            RawRecord::Cbor(bytes) => {
+                let data = value::from_cbor::<value::Data>(bytes)?;
+                Ok(serde_json::to_value(&data)?)
+            }
+        }
+    }
+}
+
+// decodes a raw record into a lexicon type; a macro rather than a generic fn
+// because the json and cbor decoders have different lifetime requirements.
+// the json is cloned so the raw record is still around if decoding fails
+macro_rules! decode {
+    ($record:expr, $ty:ty) => {
+        match $record {
+            RawRecord::Json(json) => {
+                value::from_json_value::<$ty>(json.clone()).map_err(anyhow::Error::from)
+            }
+            RawRecord::Cbor(bytes) => value::from_cbor::<$ty>(bytes).map_err(anyhow::Error::from),
+        }
+    };
+}
+
+/// A single record write or delete, from any source
+pub struct RecordOp<'a> {
+    pub did: &'a str,
+    pub collection: &'a str,
+    pub rkey: &'a str,
+    pub cid: Option<&'a str>,
+    /// Repo rev of the commit the op came from. Revs are TIDs, so they sort
+    /// in commit order, and a write only replaces rows from an older rev.
+    /// An op without one only replaces rows whose rev is unknown too.
+    pub rev: Option<&'a str>,
+    /// `None` means the record was deleted
+    pub record: Option<RawRecord<'a>>,
+}
+
+impl RecordOp<'_> {
+    pub fn uri(&self) -> String {
+        format!("at://{}/{}/{}", self.did, self.collection, self.rkey)
+    }
+}
+
+/// Which table a record was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Event,
+    Rsvp,
+    Profile,
+    /// A Bluesky profile, shown for accounts without an aktivi one
+    BskyProfile,
+}
+
+/// What the sink did with a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applied {
+    Upserted(RecordKind),
+    Deleted(RecordKind),
+    /// The index already has a newer version of the record, or deleted it at
+    /// a newer rev, so nothing was written
+    Outdated(RecordKind),
+    /// The record parsed but failed validation, so it was put in quarantine
+    /// instead of the public tables
+    Quarantined(RecordKind),
+    /// The record was for a collection we don't index, or a delete of
+    /// something we never had
+    Ignored,
+}
+
+/// The single place records are validated and written to the database.
+/// Records that parse but break the rules in [`validate`] are quarantined.
+/// Jetstream, the firehose and CAR backfill all go through here.
+#[derive(Clone)]
+pub struct RecordSink {
+    pool: PgPool,
+}
+
+impl RecordSink {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Apply a record op, storing it in `failed_records` if it can't be
+    /// indexed
+    pub async fn apply(&self, op: RecordOp<'_>) -> Result<Applied> {
+        let mut conn = self.pool.acquire().await?;
+        let result = apply(&mut conn, &op).await;
+        if let Err(e) = &result {
+            if let Err(save_err) = failed_records::save(&mut conn, &op, e).await {
+                warn!("failed to store failed record {}: {:#}", op.uri(), save_err);
+            }
+        }
+        result
+    }
+}
+
+/// Apply a record op on an existing connection, e.g. inside a transaction.
+/// Failures aren't stored here, since the caller may be about to roll back.
+/// Inside a caller's transaction the record stays locked until that commits,
+/// so it should be a short one.
+pub async fn apply(conn: &mut PgConnection, op: &RecordOp<'_>) -> Result<Applied> {
+    apply_op(conn, op, false).await
+}
+
+/// Like [`apply`], but also replaces the version stored at the op's own rev,
+/// for rebuilding rows from the records they were written from
+pub async fn reapply(conn: &mut PgConnection, op: &RecordOp<'_>) -> Result<Applied> {
+    apply_op(conn, op, true).await
+}
+
+async fn apply_op(conn: &mut PgConnection, op: &RecordOp<'_>, same_rev: bool) -> Result<Applied> {
+    let uri = op.uri();
+    let mut tx = conn.begin().await?;
+
+    // ops on the same record take turns, so a delete can't land between a
+    // write's tombstone check and its upsert. this is the only lock taken
+    // before the write, so colliding hashes just wait on each other
+    sqlx::query!("SELECT pg_advisory_xact_lock(hashtext($1))", uri)
+        .execute(&mut *tx)
+        .await?;
+
+    let applied = write(&mut tx, op, same_rev).await?;
+
+    // a later version of a record that failed or was quarantined before
+    // supersedes it
+    if matches!(applied, Applied::Upserted(_) | Applied::Deleted(_)) {
+        failed_records::clear(&mut tx, &uri).await?;
+    }
+    if matches!(applied, Applied::Upserted(_)) {
+        quarantine::clear(&mut tx, &uri, op.rev, same_rev).await?;
+    }
+
+    tx.commit().await?;
+    Ok(applied)
+}
+
+async fn write(conn: &mut PgConnection, op: &RecordOp<'_>, same_rev: bool) -> Result<Applied> {
+    let kind = match op.collection {
+        EVENT_COLLECTION => RecordKind::Event,
+        RSVP_COLLECTION => RecordKind::Rsvp,
+        PROFILE_COLLECTION => RecordKind::Profile,
+        BSKY_PROFILE_COLLECTION => RecordKind::BskyProfile,
+        _ => return Ok(Applied::Ignored),
+    };
+
+    let uri = op.uri();
+    let Some(record) = &op.record else {
+        return delete(conn, kind, op.did, &uri, op.rev).await;
+    };
+
+    let cid = op.cid.context("record write without a cid")?;
+
+    if deleted_since(conn, &uri, op.rev).await? {
+        debug!("skipping {}: deleted at a newer rev", uri);
+        return Ok(Applied::Outdated(kind));
+    }
+    if quarantine::quarantined_since(conn, &uri, op.rev, same_rev).await? {
+        debug!("skipping {}: a newer version is quarantined", uri);
+        return Ok(Applied::Outdated(kind));
+    }
+
+    let written = match kind {
+        RecordKind::Event => {
+            let event =
+                decode!(record, Event).with_context(|| format!("failed to parse event {}", uri))?;
+            let problems = validate::event(&event, Utc::now());
+            if !problems.is_empty() {
+                return quarantine(conn, op, kind, cid, record.to_json()?, &problems, same_rev)
+                    .await;
+            }
+            let raw = record.to_json()?;
+            upsert_event(conn, op, &uri, cid, same_rev, &event, &raw).await?
+        }
+        RecordKind::Rsvp => {
+            let rsvp =
+                decode!(record, Rsvp).with_context(|| format!("failed to parse rsvp {}", uri))?;
+            let problems = validate::rsvp(&rsvp);
+            if !problems.is_empty() {
+                return quarantine(conn, op, kind, cid, record.to_json()?, &problems, same_rev)
+                    .await;
+            }
+            let raw = record.to_json()?;
+            upsert_rsvp(conn, op, &uri, cid, same_rev, &rsvp, &raw).await?
+        }
+        RecordKind::Profile => {
+            // profiles are singleton records
+            if op.rkey != "self" {
+                anyhow::bail!("profile record {} has rkey other than self", uri);
+            }
+            let profile = decode!(record, Profile)
+                .with_context(|| format!("failed to parse profile {}", uri))?;
+            let problems = validate::profile(&profile);
+            if !problems.is_empty() {
+                return quarantine(conn, op, kind, cid, record.to_json()?, &problems, same_rev)
+                    .await;
+            }
+            let raw = record.to_json()?;
+            upsert_profile(conn, op.did, cid, op.rev, same_rev, &profile, &raw).await?
+        }
+        RecordKind::BskyProfile => {
+            if op.rkey != "self" {
+                anyhow::bail!("profile record {} has rkey other than self", uri);
+            }
+            let raw = record.to_json()?;
+            anyhow::ensure!(raw.is_object(), "profile record {} is not an object", uri);
+            upsert_bsky_profile(conn, op.did, cid, op.rev, &raw).await?
+        }
+    };
+
+    if !written {
+        debug!("skipping {}: already have a newer version", uri);
+        return Ok(Applied::Outdated(kind));
+    }
+
+    debug!("indexed {:?}: {}", kind, uri);
+    Ok(Applied::Upserted(kind))
+}
+
+async fn delete(
+    conn: &mut PgConnection,
+    kind: RecordKind,
+    did: &str,
+    uri: &str,
+    rev: Option<&str>,
+) -> Result<Applied> {
+    // remember the delete even if we never had the record, since an older
+    // write of it may still be on its way from a backfill
+    if let Some(rev) = rev {
+        sqlx::query!(
+            r#"
+            INSERT INTO record_tombstones (uri, did, rev)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (uri) DO UPDATE SET
+                rev = EXCLUDED.rev,
+                deleted_at = NOW()
+            WHERE record_tombstones.rev < EXCLUDED.rev
+            "#,
+            uri,
+            did,
+            rev
+        )
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let removed = remove_row(conn, kind, did, uri, rev, false).await?;
+    // rsvps pointing at a deleted event are left in place but are no longer
+    // listed, since the rsvp queries only return rsvps for indexed events
+    if removed && kind == RecordKind::Event {
+        sqlx::query!("DELETE FROM event_revisions WHERE uri = $1", uri)
+            .execute(&mut *conn)
+            .await?;
+    }
+    let unquarantined = quarantine::clear(conn, uri, rev, false).await?;
+
+    if removed || unquarantined {
+        Ok(Applied::Deleted(kind))
+    } else {
+        Ok(Applied::Ignored)
+    }
+}
+
+/// Put a record that failed validation in quarantine, taking down the
+/// version we had listed unless it's newer
+async fn quarantine(
+    conn: &mut PgConnection,
+    op: &RecordOp<'_>,
+    kind: RecordKind,
+    cid: &str,
+    record: Value,
+    problems: &[String],
+    same_rev: bool,
+) -> Result<Applied> {
+    let uri = op.uri();
+    let removed = remove_row(conn, kind, op.did, &uri, op.rev, same_rev).await?;
+    if !removed && is_indexed(conn, kind, op.did, &uri).await? {
+        return Ok(Applied::Outdated(kind));
+    }
+    if !quarantine::save(conn, op, cid, record, problems, same_rev).await? {
+        return Ok(Applied::Outdated(kind));
+    }
+
+    debug!("quarantined {}: {}", uri, problems.join("; "));
+    Ok(Applied::Quarantined(kind))
+}
+
+/// Delete a record's row from its public table if it was written at an older
+/// rev than `rev`, or at `rev` itself with `same_rev`. Returns whether a row
+/// was deleted.
+async fn remove_row(
+    conn: &mut PgConnection,
+    kind: RecordKind,
+    did: &str,
+    uri: &str,
+    rev: Option<&str>,
+    same_rev: bool,
+) -> Result<bool> {
+    let result = match kind {
+        RecordKind::Event => {
+            sqlx::query!(
+                "DELETE FROM events WHERE uri = $1 AND (rev IS NULL OR rev < $2 OR ($3 AND rev = $2))",
+                uri,
+                rev,
+                same_rev
+            )
+            .execute(&mut *conn)
+            .await?
+        }
+        RecordKind::Rsvp => {
+            sqlx::query!(
+                "DELETE FROM rsvps WHERE uri = $1 AND (rev IS NULL OR rev < $2 OR ($3 AND rev = $2))",
+                uri,
+                rev,
+                same_rev
+            )
+            .execute(&mut *conn)
+            .await?
+        }
+        // profiles are singleton records (rkey "self"), so the did is the key
+        RecordKind::Profile => {
+            sqlx::query!(
+                "DELETE FROM profiles WHERE did = $1 AND (rev IS NULL OR rev < $2 OR ($3 AND rev = $2))",
+                did,
+                rev,
+                same_rev
+            )
+            .execute(&mut *conn)
+            .await?
+        }
+        RecordKind::BskyProfile => return clear_bsky_profile(conn, did, rev).await,
+    };
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Clear the fields a deleted `app.bsky.actor.profile` record set, if they're
+/// from an older rev. The row stays for the handle the AppView reported.
+pub(crate) async fn clear_bsky_profile(
+    conn: &mut PgConnection,
+    did: &str,
+    rev: Option<&str>,
+) -> Result<bool> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE bsky_profiles SET
+            display_name = NULL,
+            description = NULL,
+            avatar_cid = NULL,
+            banner_cid = NULL,
+            cid = NULL,
+            rev = $2,
+            updated_at = NOW()
+        WHERE did = $1 AND cid IS NOT NULL AND (rev IS NULL OR rev < $2)
+        "#,
+        did,
+        rev
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Whether a record has a row in its public table
+async fn is_indexed(
+    conn: &mut PgConnection,
+    kind: RecordKind,
+    did: &str,
+    uri: &str,
+) -> Result<bool> {
+    let exists = match kind {
+        RecordKind::Event => {
+            sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM events WHERE uri = $1) AS "exists!""#,
+                uri
+            )
+            .fetch_one(&mut *conn)
+            .await?
+        }
+        RecordKind::Rsvp => {
+            sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM rsvps WHERE uri = $1) AS "exists!""#,
+                uri
+            )
+            .fetch_one(&mut *conn)
+            .await?
+        }
+        RecordKind::Profile => {
+            sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM profiles WHERE did = $1) AS "exists!""#,
+                did
+            )
+            .fetch_one(&mut *conn)
+            .await?
+        }
+        RecordKind::BskyProfile => {
+            sqlx::query_scalar!(
+                r#"SELECT EXISTS (SELECT 1 FROM bsky_profiles WHERE did = $1 AND cid IS NOT NULL) AS "exists!""#,
+                did
+            )
+            .fetch_one(&mut *conn)
+            .await?
+        }
+    };
+
+    Ok(exists)
+}
+
+/// Whether the record was deleted at a rev at or after `rev`
+async fn deleted_since(conn: &mut PgConnection, uri: &str, rev: Option<&str>) -> Result<bool> {
+    let Some(rev) = rev else {
+        return Ok(false);
+    };
+
+    let deleted = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM record_tombstones WHERE uri = $1 AND rev >= $2) AS "deleted!""#,
+        uri,
+        rev
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(deleted)
+}
+
+// the upserts below only touch an existing row from an older rev than the
+// incoming write, or the same rev with `same_rev`, and return whether
+// anything was written
+
+async fn upsert_event(
+    conn: &mut PgConnection,
+    op: &RecordOp<'_>,
+    uri: &str,
+    cid: &str,
+    same_rev: bool,
+    event: &Event<'_>,
+    record: &Value,
+) -> Result<bool> {
+    let created_at = event.created_at.as_ref();
+    let starts_at = event.starts_at.as_ref().map(|dt| dt.as_ref());
+    let ends_at = event.ends_at.as_ref().map(|dt| dt.as_ref());
+
+    // serialize locations and uris to json
+    let locations = event
+        .locations
+        .as_ref()
+        .map(serde_json::to_value)
+        .transpose()?;
+    let uris = event.uris.as_ref().map(serde_json::to_value).transpose()?;
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO events (uri, cid, did, rkey, name, description, created_at, starts_at, ends_at, mode, status, locations, uris, rev, record)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+        ON CONFLICT (uri) DO UPDATE SET
+            cid = EXCLUDED.cid,
+            name = EXCLUDED.name,
+            description = EXCLUDED.description,
+            created_at = EXCLUDED.created_at,
+            starts_at = EXCLUDED.starts_at,
+            ends_at = EXCLUDED.ends_at,
+            mode = EXCLUDED.mode,
+            status = EXCLUDED.status,
+            locations = EXCLUDED.locations,
+            uris = EXCLUDED.uris,
+            rev = EXCLUDED.rev,
+            record = EXCLUDED.record
+        WHERE events.rev IS NULL OR events.rev < EXCLUDED.rev
+           OR ($16 AND events.rev = EXCLUDED.rev)
+        "#,
+        uri,
+        cid,
+        op.did,
+        op.rkey,
+        event.name.as_ref(),
+        event.description.as_ref().map(|d| d.as_ref()),
+        created_at,
+        starts_at,
+        ends_at,
+        event.mode.as_ref().map(|m| m.as_ref()),
+        event.status.as_ref().map(|s| s.as_ref()),
+        locations,
+        uris,
+        op.rev,
+        record,
+        same_rev,
+    )
+    .execute(&mut *conn)
+    .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    // keep every version, so rsvps against an older cid can see what changed.
+    // writes without a rev never conflict on (uri, rev), so those skip a
+    // version we already have by its cid
+    sqlx::query!(
+        r#"
+        INSERT INTO event_revisions (uri, rev, cid, did, name, description, starts_at, ends_at, mode, status, locations, uris, record)
+        SELECT uri, rev, cid, did, name, description, starts_at, ends_at, mode, status, locations, uris, record
+        FROM events
+        WHERE uri = $1
+          AND (rev IS NOT NULL OR NOT EXISTS (
+              SELECT 1 FROM event_revisions er
+              WHERE er.uri = events.uri AND er.rev IS NULL AND er.cid = events.cid
+          ))
+        ON CONFLICT (uri, rev) DO NOTHING
+        "#,
+        uri,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(true)
+}
+
+async fn upsert_rsvp(
+    conn: &mut PgConnection,
+    op: &RecordOp<'_>,
+    uri: &str,
+    cid: &str,
+    same_rev: bool,
+    rsvp: &Rsvp<'_>,
+    record: &Value,
+) -> Result<bool> {
+    let (subject_uri, subject_cid) = subject_ref(&rsvp.subject)
+        .with_context(|| format!("rsvp {} has an invalid subject strongRef", uri))?;
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO rsvps (uri, cid, did, rkey, subject_uri, subject_cid, status, rev, record)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (uri) DO UPDATE SET
+            cid = EXCLUDED.cid,
+            subject_uri = EXCLUDED.subject_uri,
+            subject_cid = EXCLUDED.subject_cid,
+            status = EXCLUDED.status,
+            rev = EXCLUDED.rev,
+            record = EXCLUDED.record
+        WHERE rsvps.rev IS NULL OR rsvps.rev < EXCLUDED.rev
+           OR ($10 AND rsvps.rev = EXCLUDED.rev)
+        "#,
+        uri,
+        cid,
+        op.did,
+        op.rkey,
+        subject_uri,
+        subject_cid,
+        rsvp.status.as_ref(),
+        op.rev,
+        record,
+        same_rev,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+async fn upsert_profile(
+    conn: &mut PgConnection,
+    did: &str,
+    cid: &str,
+    rev: Option<&str>,
+    same_rev: bool,
+    profile: &Profile<'_>,
+    record: &Value,
+) -> Result<bool> {
+    // the typed record only tells us a blob is there; read the ref from the json
+    let avatar = BlobRef::from_record(record, "avatar");
+    let banner = BlobRef::from_record(record, "banner");
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO profiles (did, display_name, description, avatar_cid, avatar_mime,
+                              banner_cid, banner_mime, cid, rev, record)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (did) DO UPDATE SET
+            display_name = EXCLUDED.display_name,
+            description = EXCLUDED.description,
+            avatar_cid = EXCLUDED.avatar_cid,
+            avatar_mime = EXCLUDED.avatar_mime,
+            banner_cid = EXCLUDED.banner_cid,
+            banner_mime = EXCLUDED.banner_mime,
+            cid = EXCLUDED.cid,
+            rev = EXCLUDED.rev,
+            record = EXCLUDED.record,
+            updated_at = NOW()
+        WHERE profiles.rev IS NULL OR profiles.rev < EXCLUDED.rev
+           OR ($11 AND profiles.rev = EXCLUDED.rev)
+        "#,
+        did,
+        profile.display_name.as_ref().map(|n| n.as_ref()),
+        profile.description.as_ref().map(|d| d.as_ref()),
+        avatar.as_ref().map(|b| b.cid.as_str()),
+        avatar.as_ref().map(|b| b.mime_type.as_str()),
+        banner.as_ref().map(|b| b.cid.as_str()),
+        banner.as_ref().map(|b| b.mime_type.as_str()),
+        cid,
+        rev,
+        record,
+        same_rev,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Store a Bluesky profile record. It takes over from whatever the AppView
+/// reported for the account, but keeps the handle.
+async fn upsert_bsky_profile(
+    conn: &mut PgConnection,
+    did: &str,
+    cid: &str,
+    rev: Option<&str>,
+    record: &Value,
+) -> Result<bool> {
+    let text = |field: &str| record.get(field).and_then(|v| v.as_str());
+    let avatar = BlobRef::from_record(record, "avatar");
+    let banner = BlobRef::from_record(record, "banner");
+
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO bsky_profiles (did, source, display_name, description, avatar_cid,
+                                   banner_cid, cid, rev)
+        VALUES ($1, 'record', $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (did) DO UPDATE SET
+            source = EXCLUDED.source,
+            display_name = EXCLUDED.display_name,
+            description = EXCLUDED.description,
+            avatar_cid = EXCLUDED.avatar_cid,
+            banner_cid = EXCLUDED.banner_cid,
+            cid = EXCLUDED.cid,
+            rev = EXCLUDED.rev,
+            updated_at = NOW()
+        WHERE bsky_profiles.rev IS NULL OR bsky_profiles.rev < EXCLUDED.rev
+        "#,
+        did,
+        text("displayName"),
+        text("description"),
+        avatar.as_ref().map(|b| b.cid.as_str()),
+        banner.as_ref().map(|b| b.cid.as_str()),
+        cid,
+        rev,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drop tombstones older than `max_age`; by then any backfill that could
+/// still carry the deleted record has long finished
+pub async fn prune_tombstones(pool: &PgPool, max_age: Duration) -> Result<u64> {
+    let result = sqlx::query!(
+        "DELETE FROM record_tombstones WHERE deleted_at < NOW() - make_interval(secs => $1)",
+        max_age.as_secs() as f64
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Extract uri and cid from an rsvp's strongRef subject
+fn subject_ref<'r>(subject: &'r value::Data<'_>) -> Option<(&'r str, &'r str)> {
+    let value::Data::Object(obj) = subject else {
+        return None;
+    };
+
+    let uri = obj.get("uri").and_then(|v| match v {
+        value::Data::String(s) => Some(s.as_ref()),
+        _ => None,
+    })?;
+    let cid = obj.get("cid").and_then(|v| match v {
+        value::Data::String(s) => Some(s.as_ref()),
+        _ => None,
+    })?;
+
+    Some((uri, cid))
+}
@@ -0,0 +1,214 @@
+use anyhow::{Context, Result};
+use sqlx::PgPool;
+use std::{sync::LazyLock, time::Duration};
+use tracing::{info, warn};
+
+use crate::{
+    backfill::{pds_endpoint, resolve_did_doc},
+    backfill_jobs::{backoff, MAX_ATTEMPTS},
+    mst,
+    sink::{Applied, RawRecord, RecordOp, RecordSink, EVENT_COLLECTION},
+    verify::{CarVerifier, SigningKey},
+};
+
+/// How many subjects a single resolver pass fetches
+const BATCH_SIZE: i64 = 50;
+
+/// Largest `sync.getRecord` CAR accepted. It only holds a commit, one MST
+/// path and the record
+const MAX_PROOF_BYTES: u64 = 1024 * 1024;
+
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .expect("failed to build http client")
+});
+
+#[derive(Debug)]
+struct SubjectFetch {
+    uri: String,
+    cid: String,
+    attempts: i32,
+}
+
+/// Finds rsvps whose subject event we've never indexed and fetches the event
+/// from its author's PDS.
+/// Subjects that can't be fetched are retried with backoff. Returns (resolved, failed).
+pub async fn resolve_dangling(pool: &PgPool) -> Result<(usize, usize)> {
+    // pick up newly dangling subjects, and forget ones that were indexed
+    // some other way (live ingestion, backfill) since the last pass
+    sqlx::query!(
+        r#"
+        INSERT INTO subject_fetches (uri, cid)
+        SELECT DISTINCT ON (r.subject_uri) r.subject_uri, r.subject_cid
+        FROM rsvps r
+        WHERE NOT EXISTS (SELECT 1 FROM events e WHERE e.uri = r.subject_uri)
+          AND NOT EXISTS (SELECT 1 FROM quarantined_records q WHERE q.uri = r.subject_uri)
+        ON CONFLICT (uri) DO NOTHING
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM subject_fetches f
+        WHERE EXISTS (SELECT 1 FROM events e WHERE e.uri = f.uri)
+           OR EXISTS (SELECT 1 FROM quarantined_records q WHERE q.uri = f.uri)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    let due = sqlx::query_as!(
+        SubjectFetch,
+        r#"
+        SELECT uri, cid, attempts
+        FROM subject_fetches
+        WHERE attempts < $1 AND run_after <= NOW()
+        ORDER BY run_after
+        LIMIT $2
+        "#,
+        MAX_ATTEMPTS,
+        BATCH_SIZE
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let sink = RecordSink::new(pool.clone());
+    let mut resolved = 0;
+    let mut failed = 0;
+
+    for subject in due {
+        match fetch_subject(&sink, &subject.uri, &subject.cid).await {
+            Ok(()) => {
+                sqlx::query!("DELETE FROM subject_fetches WHERE uri = $1", &subject.uri)
+                    .execute(pool)
+                    .await?;
+                info!("resolved rsvp subject {}", subject.uri);
+                resolved += 1;
+            }
+            Err(e) => {
+                let err = format!("{:#}", e);
+                let attempts = subject.attempts + 1;
+                sqlx::query!(
+                    r#"
+                    UPDATE subject_fetches
+                    SET attempts = $2,
+                        last_error = $3,
+                        run_after = NOW() + make_interval(secs => $4),
+                        updated_at = NOW()
+                    WHERE uri = $1
+                    "#,
+                    &subject.uri,
+                    attempts,
+                    err,
+                    backoff(attempts).as_secs() as f64
+                )
+                .execute(pool)
+                .await?;
+                warn!(
+                    "failed to resolve rsvp subject {} (attempt {}/{}): {}",
+                    subject.uri, attempts, MAX_ATTEMPTS, err
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((resolved, failed))
+}
+
+/// Fetch the event at `uri` from its author's PDS and index it. The PDS only
+/// serves the current version, so an event edited since the rsvp was made
+/// is rejected rather than indexed as something the rsvp never pointed at;
+/// the mismatch ends up in the fetch's `last_error`.
+async fn fetch_subject(sink: &RecordSink, uri: &str, cid: &str) -> Result<()> {
+    let (did, collection, rkey) = parse_at_uri(uri).context("malformed subject uri")?;
+    if collection != EVENT_COLLECTION {
+        anyhow::bail!("subject is a {} record, not an event", collection);
+    }
+
+    let doc = resolve_did_doc(did).await?;
+    let pds = pds_endpoint(&doc)?;
+    let signing_key = SigningKey::from_did_doc(&doc)
+        .with_context(|| format!("failed to get signing key for {}", did))?;
+
+    // sync.getRecord returns the record with its MST path and signed commit,
+    // so it can be checked like any other repo CAR
+    let mut response = HTTP_CLIENT
+        .get(format!("{}/xrpc/com.atproto.sync.getRecord", pds))
+        .query(&[("did", did), ("collection", collection), ("rkey", rkey)])
+        .send()
+        .await?
+        .error_for_status()?;
+
+    let mut verifier = CarVerifier::new()
+        .keep_blocks()
+        .with_max_bytes(MAX_PROOF_BYTES);
+    while let Some(chunk) = response.chunk().await? {
+        verifier.update(&chunk)?;
+    }
+    let commit = verifier
+        .finish(did, Some(&signing_key))
+        .with_context(|| format!("record verification failed for {}", uri))?;
+    let blocks = verifier.take_blocks();
+
+    let current = mst::lookup(commit.data, &blocks, &format!("{}/{}", collection, rkey))?
+        .context("event no longer exists")?;
+    let record = blocks
+        .get(&current)
+        .context("record block missing from proof")?;
+
+    let current = current.to_string();
+    if current != cid {
+        anyhow::bail!(
+            "event was edited since the rsvp was made: it is now {}, not {}",
+            current,
+            cid
+        );
+    }
+
+    let op = RecordOp {
+        did,
+        collection,
+        rkey,
+        cid: Some(&current),
+        rev: Some(&commit.rev),
+        record: Some(RawRecord::Cbor(record)),
+    };
+    match sink.apply(op).await? {
+        // outdated means a newer version got indexed in the meantime; a
+        // quarantined subject isn't worth fetching again
+        Applied::Upserted(_) | Applied::Outdated(_) | Applied::Quarantined(_) => Ok(()),
+        other => anyhow::bail!("subject was not indexed: {:?}", other),
+    }
+}
+
+/// Split `at://did/collection/rkey` into its parts
+fn parse_at_uri(uri: &str) -> Option<(&str, &str, &str)> {
+    let mut parts = uri.strip_prefix("at://")?.splitn(3, '/');
+    let did = parts.next()?;
+    let collection = parts.next()?;
+    let rkey = parts.next()?;
+
+    if did.is_empty() || collection.is_empty() || rkey.is_empty() || rkey.contains('/') {
+        return None;
+    }
+    Some((did, collection, rkey))
+}
+
+#[test]
+fn test_parse_at_uri() {
+    assert_eq!(
+        parse_at_uri("at://did:plc:abc/community.lexicon.calendar.event/3kaaa"),
+        Some(("did:plc:abc", "community.lexicon.calendar.event", "3kaaa"))
+    );
+    assert_eq!(
+        parse_at_uri("at://did:plc:abc/community.lexicon.calendar.event"),
+        None
+    );
+    assert_eq!(parse_at_uri("https://did:plc:abc/a/b"), None);
+    assert_eq!(parse_at_uri("at://did:plc:abc/a/b/c"), None);
+}
@@ -0,0 +1,263 @@
+use chrono::{DateTime, Datelike, FixedOffset, Utc};
+use jacquard_common::types::string::Datetime;
+use jacquard_lexicon::{
+    lexicon::{LexArray, LexObjectProperty, LexRecordRecord, LexString, LexUserType},
+    schema::LexiconSchema,
+};
+use lex_rs::co_aktivi::actor::profile::Profile;
+use lex_rs::community_lexicon::calendar::{
+    event::{Event, Mode, Status},
+    rsvp::Rsvp,
+};
+use serde::Serialize;
+use serde_json::Value;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Longest event name we'll list, in graphemes. The lexicon sets no limit.
+pub const MAX_NAME_GRAPHEMES: usize = 300;
+
+/// Longest event description we'll list, in graphemes
+pub const MAX_DESCRIPTION_GRAPHEMES: usize = 10_000;
+
+/// Event dates before this year are assumed to be typos
+const MIN_YEAR: i32 = 1900;
+
+/// How many years ahead of now an event date may be
+const MAX_YEARS_AHEAD: i32 = 10;
+
+/// Why an event should be quarantined rather than listed; empty if it's fine
+pub fn event(event: &Event<'_>, now: DateTime<Utc>) -> Vec<String> {
+    let mut problems = schema(event);
+
+    if event.name.trim().is_empty() {
+        problems.push("name is empty".to_string());
+    }
+    check_graphemes("name", &event.name, MAX_NAME_GRAPHEMES, &mut problems);
+    if let Some(description) = &event.description {
+        check_graphemes(
+            "description",
+            description,
+            MAX_DESCRIPTION_GRAPHEMES,
+            &mut problems,
+        );
+    }
+
+    let dates = [
+        ("createdAt", Some(&event.created_at)),
+        ("startsAt", event.starts_at.as_ref()),
+        ("endsAt", event.ends_at.as_ref()),
+    ];
+    for (field, value) in dates {
+        if let Some(value) = value {
+            check_year(field, value, now, &mut problems);
+        }
+    }
+
+    if let (Some(starts_at), Some(ends_at)) = (&event.starts_at, &event.ends_at) {
+        let starts_at: &DateTime<FixedOffset> = starts_at.as_ref();
+        let ends_at: &DateTime<FixedOffset> = ends_at.as_ref();
+        if ends_at < starts_at {
+            problems.push(format!(
+                "endsAt {} is before startsAt {}",
+                ends_at.to_rfc3339(),
+                starts_at.to_rfc3339()
+            ));
+        }
+    }
+
+    // the generated enums only fall back to Other for values outside the
+    // lexicon's knownValues
+    if let Some(Mode::Other(mode)) = &event.mode {
+        problems.push(format!("unknown mode {:?}", mode.as_ref()));
+    }
+    if let Some(Status::Other(status)) = &event.status {
+        problems.push(format!("unknown status {:?}", status.as_ref()));
+    }
+
+    problems
+}
+
+/// Why an rsvp should be quarantined rather than listed; empty if it's fine
+pub fn rsvp(rsvp: &Rsvp<'_>) -> Vec<String> {
+    let mut problems = schema(rsvp);
+
+    // the rsvp lexicon defines exactly its status values as tokens
+    if !tokens::<Rsvp>().iter().any(|t| t == rsvp.status.as_ref()) {
+        problems.push(format!("unknown status {:?}", rsvp.status.as_ref()));
+    }
+
+    problems
+}
+
+/// Why a profile should be quarantined rather than listed; empty if it's fine
+pub fn profile(profile: &Profile<'_>) -> Vec<String> {
+    schema(profile)
+}
+
+/// Check a record's top-level fields against the limits in its generated
+/// lexicon doc
+fn schema<T: LexiconSchema + Serialize>(record: &T) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let doc = T::lexicon_doc();
+    let Some(LexUserType::Record(main)) = doc.defs.get("main") else {
+        return problems;
+    };
+    let LexRecordRecord::Object(object) = &main.record;
+    let Ok(Value::Object(json)) = serde_json::to_value(record) else {
+        return problems;
+    };
+
+    for (field, property) in &object.properties {
+        match (property, json.get(field.as_str())) {
+            (LexObjectProperty::String(schema), Some(Value::String(value))) => {
+                check_string(field, schema, value, &mut problems)
+            }
+            (LexObjectProperty::Array(schema), Some(Value::Array(items))) => {
+                check_array(field, schema, items.len(), &mut problems)
+            }
+            _ => {}
+        }
+    }
+
+    problems
+}
+
+fn check_string(field: &str, schema: &LexString<'_>, value: &str, problems: &mut Vec<String>) {
+    // lexicon lengths are in utf-8 bytes
+    let len = value.len();
+    if schema.max_length.is_some_and(|max| len > max)
+        || schema.min_length.is_some_and(|min| len < min)
+    {
+        problems.push(format!(
+            "{} is {} bytes, outside the lexicon's {:?}..{:?}",
+            field, len, schema.min_length, schema.max_length
+        ));
+    }
+
+    if schema.max_graphemes.is_some() || schema.min_graphemes.is_some() {
+        let graphemes = value.graphemes(true).count();
+        if schema.max_graphemes.is_some_and(|max| graphemes > max)
+            || schema.min_graphemes.is_some_and(|min| graphemes < min)
+        {
+            problems.push(format!(
+                "{} is {} graphemes, outside the lexicon's {:?}..{:?}",
+                field, graphemes, schema.min_graphemes, schema.max_graphemes
+            ));
+        }
+    }
+
+    if let Some(allowed) = &schema.r#enum {
+        if !allowed.iter().any(|a| a.as_ref() == value) {
+            problems.push(format!("{} {:?} is not an allowed value", field, value));
+        }
+    }
+}
+
+fn check_array(field: &str, schema: &LexArray<'_>, len: usize, problems: &mut Vec<String>) {
+    if schema.max_length.is_some_and(|max| len > max)
+        || schema.min_length.is_some_and(|min| len < min)
+    {
+        problems.push(format!(
+            "{} has {} items, outside the lexicon's {:?}..{:?}",
+            field, len, schema.min_length, schema.max_length
+        ));
+    }
+}
+
+fn check_graphemes(field: &str, value: &str, max: usize, problems: &mut Vec<String>) {
+    let graphemes = value.graphemes(true).count();
+    if graphemes > max {
+        problems.push(format!(
+            "{} is {} graphemes, over the limit of {}",
+            field, graphemes, max
+        ));
+    }
+}
+
+fn check_year(field: &str, value: &Datetime, now: DateTime<Utc>, problems: &mut Vec<String>) {
+    let value: &DateTime<FixedOffset> = value.as_ref();
+    let max_year = now.year() + MAX_YEARS_AHEAD;
+    if value.year() < MIN_YEAR || value.year() > max_year {
+        problems.push(format!(
+            "{} {} is outside the years {} to {}",
+            field,
+            value.to_rfc3339(),
+            MIN_YEAR,
+            max_year
+        ));
+    }
+}
+
+/// Every token defined in a record's lexicon, as `nsid#name`
+fn tokens<T: LexiconSchema>() -> Vec<String> {
+    T::lexicon_doc()
+        .defs
+        .iter()
+        .filter(|(_, def)| matches!(def, LexUserType::Token(_)))
+        .map(|(name, _)| format!("{}#{}", T::nsid(), name))
+        .collect()
+}
+
+#[test]
+fn test_event_rules() {
+    use jacquard::types::value::from_json_value;
+
+    let now = "2025-06-01T00:00:00Z".parse().unwrap();
+    let valid = from_json_value::<Event>(serde_json::json!({
+        "name": "Meetup",
+        "createdAt": "2025-05-01T00:00:00Z",
+        "startsAt": "2025-06-08T18:00:00Z",
+        "endsAt": "2025-06-08T20:00:00Z",
+        "mode": "community.lexicon.calendar.event#inperson",
+        "status": "community.lexicon.calendar.event#scheduled",
+    }))
+    .unwrap();
+    assert!(event(&valid, now).is_empty());
+
+    let invalid = from_json_value::<Event>(serde_json::json!({
+        "name": "x".repeat(MAX_NAME_GRAPHEMES + 1),
+        "createdAt": "2025-05-01T00:00:00Z",
+        "startsAt": "9999-06-08T18:00:00Z",
+        "endsAt": "2025-06-08T20:00:00Z",
+        "mode": "community.lexicon.calendar.event#underwater",
+    }))
+    .unwrap();
+    let problems = event(&invalid, now);
+    assert_eq!(problems.len(), 4, "{:?}", problems);
+    assert!(problems[0].starts_with("name"));
+    assert!(problems[1].starts_with("startsAt"));
+    assert!(problems[2].starts_with("endsAt"));
+    assert!(problems[3].starts_with("unknown mode"));
+}
+
+#[test]
+fn test_schema_limits_and_tokens() {
+    use jacquard::types::value::from_json_value;
+
+    // displayName is capped at 64 graphemes in co.aktivi.actor.profile
+    let long_name = from_json_value::<Profile>(serde_json::json!({
+        "displayName": "🎉".repeat(65),
+    }))
+    .unwrap();
+    let problems = profile(&long_name);
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(problems[0].starts_with("displayName is 65 graphemes"));
+
+    let subject = serde_json::json!({
+        "uri": "at://did:plc:abc/community.lexicon.calendar.event/3laaaaaaaaa21",
+        "cid": "bafyreia",
+    });
+    let going = from_json_value::<Rsvp>(serde_json::json!({
+        "subject": subject,
+        "status": "community.lexicon.calendar.rsvp#going",
+    }))
+    .unwrap();
+    assert!(rsvp(&going).is_empty());
+    let maybe = from_json_value::<Rsvp>(serde_json::json!({
+        "subject": subject,
+        "status": "community.lexicon.calendar.rsvp#maybe",
+    }))
+    .unwrap();
+    assert_eq!(rsvp(&maybe).len(), 1);
+}
@@ -0,0 +1,501 @@
+use anyhow::{Context, Result};
+use ipld_core::{cid::Cid, ipld::Ipld};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::{self, Cursor},
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{ready, Context as TaskContext, Poll},
+};
+use tokio::io::{AsyncRead, ReadBuf};
+
+// multicodec prefixes (varint encoded) for the key types atproto allows
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+const P256_PUB: [u8; 2] = [0x80, 0x24];
+
+// multihash code for sha2-256
+const SHA2_256: u64 = 0x12;
+
+/// A public key used to sign repo commits
+#[derive(Debug, Clone)]
+pub enum SigningKey {
+    K256(k256::ecdsa::VerifyingKey),
+    P256(p256::ecdsa::VerifyingKey),
+}
+
+impl SigningKey {
+    /// Parse a `publicKeyMultibase` value from a DID document (Multikey format)
+    pub fn from_multibase(multibase: &str) -> Result<Self> {
+        let encoded = multibase
+            .strip_prefix('z')
+            .context("public key is not base58btc multibase")?;
+        let bytes = bs58::decode(encoded)
+            .into_vec()
+            .context("invalid base58 public key")?;
+
+        if let Some(key) = bytes.strip_prefix(&SECP256K1_PUB) {
+            let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                .context("invalid secp256k1 public key")?;
+            Ok(SigningKey::K256(key))
+        } else if let Some(key) = bytes.strip_prefix(&P256_PUB) {
+            let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(key)
+                .context("invalid p256 public key")?;
+            Ok(SigningKey::P256(key))
+        } else {
+            anyhow::bail!("unsupported public key type")
+        }
+    }
+
+    /// Find the `#atproto` verification key in a DID document
+    pub fn from_did_doc(doc: &serde_json::Value) -> Result<Self> {
+        let multibase = doc
+            .get("verificationMethod")
+            .and_then(|v| v.as_array())
+            .and_then(|methods| {
+                methods.iter().find(|m| {
+                    m.get("id")
+                        .and_then(|id| id.as_str())
+                        .is_some_and(|id| id.ends_with("#atproto"))
+                })
+            })
+            .and_then(|m| m.get("publicKeyMultibase"))
+            .and_then(|k| k.as_str())
+            .context("no #atproto verification key in DID document")?;
+
+        Self::from_multibase(multibase)
+    }
+
+    /// Verify a compact (r||s) low-S ECDSA signature over `data`
+    fn verify(&self, data: &[u8], sig: &[u8]) -> Result<()> {
+        use p256::ecdsa::signature::Verifier;
+
+        match self {
+            SigningKey::K256(key) => {
+                let sig = k256::ecdsa::Signature::from_slice(sig).context("malformed signature")?;
+                if sig.normalize_s().is_some() {
+                    anyhow::bail!("signature is not low-S");
+                }
+                key.verify(data, &sig).context("invalid signature")
+            }
+            SigningKey::P256(key) => {
+                let sig = p256::ecdsa::Signature::from_slice(sig).context("malformed signature")?;
+                if sig.normalize_s().is_some() {
+                    anyhow::bail!("signature is not low-S");
+                }
+                key.verify(data, &sig).context("invalid signature")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CarHeader {
+    roots: Vec<Cid>,
+    version: u64,
+}
+
+/// Verify a repo CAR export before any of its records are indexed.
+/// See [`CarVerifier`] for what is checked.
+pub fn verify_repo(did: &str, car: &[u8], key: &SigningKey) -> Result<VerifiedCommit> {
+    let mut verifier = CarVerifier::new();
+    verifier.update(car)?;
+    verifier.finish(did, Some(key))
+}
+
+/// A repo commit whose signature has been checked
+#[derive(Debug, Clone)]
+pub struct VerifiedCommit {
+    pub cid: Cid,
+    pub rev: String,
+    /// Root of the commit's MST
+    pub data: Cid,
+}
+
+/// Checks a repo CAR export as it arrives, without holding the whole file:
+///
+/// - every block must hash to the CID it is stored under, so the MST walk
+///   from the commit's `data` root only ever sees authentic nodes and records
+/// - the root block must be a v3 commit for `did`
+/// - the commit signature must verify against the account's `#atproto` key
+///
+/// Together these tie every record we index to a signature by the account.
+/// Feed it bytes with [`update`](Self::update) and call
+/// [`finish`](Self::finish) once the CAR has been read to the end.
+#[derive(Debug, Default)]
+pub struct CarVerifier {
+    // bytes of a section that hasn't fully arrived yet
+    pending: Vec<u8>,
+    root: Option<Cid>,
+    commit_block: Option<Vec<u8>>,
+    blocks: Option<HashMap<Cid, Vec<u8>>>,
+    total_bytes: u64,
+    max_bytes: Option<u64>,
+}
+
+impl CarVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep every verified block, for walking a partial MST afterwards
+    pub fn keep_blocks(mut self) -> Self {
+        self.blocks = Some(HashMap::new());
+        self
+    }
+
+    /// Reject CARs larger than `max_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Feed the next bytes of the CAR, checking every section they complete
+    pub fn update(&mut self, data: &[u8]) -> Result<()> {
+        self.total_bytes += data.len() as u64;
+        if let Some(max) = self.max_bytes {
+            if self.total_bytes > max {
+                anyhow::bail!("repo is larger than the {} byte limit", max);
+            }
+        }
+
+        self.pending.extend_from_slice(data);
+
+        let mut consumed = 0;
+        while let Some((len, varint_len)) = peek_varint(&self.pending[consumed..])? {
+            let len = usize::try_from(len).context("CAR section length overflows")?;
+            if let Some(max) = self.max_bytes {
+                if len as u64 > max {
+                    anyhow::bail!(
+                        "CAR section of {} bytes is over the {} byte limit",
+                        len,
+                        max
+                    );
+                }
+            }
+            let start = consumed + varint_len;
+            let end = start
+                .checked_add(len)
+                .context("CAR section length overflows")?;
+            if end > self.pending.len() {
+                break;
+            }
+
+            let section = self.pending[start..end].to_vec();
+            self.section(&section)?;
+            consumed = end;
+        }
+
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+
+    fn section(&mut self, section: &[u8]) -> Result<()> {
+        // the first section is the header
+        let Some(root) = self.root else {
+            let header: CarHeader =
+                serde_ipld_dagcbor::from_slice(section).context("invalid CAR header")?;
+            if header.version != 1 {
+                anyhow::bail!("unsupported CAR version {}", header.version);
+            }
+            self.root = Some(*header.roots.first().context("CAR has no root")?);
+            return Ok(());
+        };
+
+        let mut reader = Cursor::new(section);
+        let cid = Cid::read_bytes(&mut reader).context("invalid block CID")?;
+        let data = &section[reader.position() as usize..];
+
+        verify_block(&cid, data)?;
+        if cid == root {
+            self.commit_block = Some(data.to_vec());
+        }
+        if let Some(blocks) = &mut self.blocks {
+            blocks.insert(cid, data.to_vec());
+        }
+
+        Ok(())
+    }
+
+    /// Whether more than the [`with_max_bytes`](Self::with_max_bytes) limit
+    /// was fed in
+    pub fn over_limit(&self) -> bool {
+        self.max_bytes.is_some_and(|max| self.total_bytes > max)
+    }
+
+    /// The DID named in the commit block, once it has been read
+    pub fn commit_did(&self) -> Result<Option<String>> {
+        let Some(block) = &self.commit_block else {
+            return Ok(None);
+        };
+
+        match serde_ipld_dagcbor::from_slice::<Ipld>(block).context("invalid commit block")? {
+            Ipld::Map(commit) => match commit.get("did") {
+                Some(Ipld::String(did)) => Ok(Some(did.clone())),
+                _ => anyhow::bail!("commit has no did"),
+            },
+            _ => anyhow::bail!("commit block is not a map"),
+        }
+    }
+
+    /// Check the commit once every block has been read. With no `key` the
+    /// commit is checked but its signature isn't, e.g. for offline imports.
+    pub fn finish(&self, did: &str, key: Option<&SigningKey>) -> Result<VerifiedCommit> {
+        if !self.pending.is_empty() {
+            anyhow::bail!("truncated CAR block");
+        }
+        let root = self.root.context("truncated CAR header")?;
+        let commit_block = self
+            .commit_block
+            .as_ref()
+            .context("commit block missing from CAR")?;
+
+        verify_commit(did, root, commit_block, key)
+    }
+
+    /// The blocks kept by [`keep_blocks`](Self::keep_blocks)
+    pub fn take_blocks(&mut self) -> HashMap<Cid, Vec<u8>> {
+        self.blocks.take().unwrap_or_default()
+    }
+}
+
+/// Passes a CAR stream through to `inner`'s reader while feeding every byte to
+/// a shared [`CarVerifier`]. Bad blocks and oversized repos fail the read.
+pub struct VerifyingReader<R> {
+    inner: R,
+    verifier: Arc<Mutex<CarVerifier>>,
+}
+
+impl<R> VerifyingReader<R> {
+    pub fn new(inner: R, verifier: Arc<Mutex<CarVerifier>>) -> Self {
+        Self { inner, verifier }
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for VerifyingReader<R> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let before = buf.filled().len();
+        ready!(Pin::new(&mut self.inner).poll_read(cx, buf))?;
+
+        let mut verifier = self.verifier.lock().expect("car verifier lock poisoned");
+        verifier
+            .update(&buf.filled()[before..])
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:#}", e)))?;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Check that a block's contents hash to its CID
+pub fn verify_block(cid: &Cid, data: &[u8]) -> Result<()> {
+    if cid.hash().code() != SHA2_256 {
+        anyhow::bail!(
+            "block {} uses unsupported hash 0x{:x}",
+            cid,
+            cid.hash().code()
+        );
+    }
+
+    let hash = Sha256::digest(data);
+    if cid.hash().digest() != hash.as_slice() {
+        anyhow::bail!("block contents do not match CID {}", cid);
+    }
+
+    Ok(())
+}
+
+/// Check a commit block belongs to `did` and is signed by `key`, if given
+pub fn verify_commit(
+    did: &str,
+    cid: Cid,
+    commit_block: &[u8],
+    key: Option<&SigningKey>,
+) -> Result<VerifiedCommit> {
+    let Ipld::Map(mut commit) =
+        serde_ipld_dagcbor::from_slice::<Ipld>(commit_block).context("invalid commit block")?
+    else {
+        anyhow::bail!("commit block is not a map");
+    };
+
+    match commit.get("version") {
+        Some(Ipld::Integer(3)) => {}
+        other => anyhow::bail!("unsupported commit version: {:?}", other),
+    }
+
+    match commit.get("did") {
+        Some(Ipld::String(commit_did)) if commit_did == did => {}
+        Some(Ipld::String(commit_did)) => {
+            anyhow::bail!("commit is for {}, expected {}", commit_did, did)
+        }
+        _ => anyhow::bail!("commit has no did"),
+    }
+
+    let rev = match commit.get("rev") {
+        Some(Ipld::String(rev)) => rev.clone(),
+        _ => anyhow::bail!("commit has no rev"),
+    };
+
+    let data = match commit.get("data") {
+        Some(Ipld::Link(data)) => *data,
+        _ => anyhow::bail!("commit has no data root"),
+    };
+
+    let Some(Ipld::Bytes(sig)) = commit.remove("sig") else {
+        anyhow::bail!("commit is not signed");
+    };
+
+    if let Some(key) = key {
+        // the signature covers the dag-cbor encoding of the commit without `sig`
+        let unsigned = serde_ipld_dagcbor::to_vec(&Ipld::Map(commit))?;
+        key.verify(&unsigned, &sig)
+            .context("commit signature does not match the #atproto key")?;
+    }
+
+    Ok(VerifiedCommit { cid, rev, data })
+}
+
+/// Read a varint from the start of `buf`, returning the value and its length,
+/// or `None` if `buf` ends partway through it
+fn peek_varint(buf: &[u8]) -> Result<Option<(u64, usize)>> {
+    let mut value: u64 = 0;
+
+    for (i, byte) in buf.iter().enumerate() {
+        if i >= 10 {
+            anyhow::bail!("varint overflow");
+        }
+
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok(Some((value, i + 1)));
+        }
+    }
+
+    Ok(None)
+}
+
+#[test]
+fn test_signing_key_from_multibase() {
+    // examples from the atproto cryptography spec
+    assert!(matches!(
+        SigningKey::from_multibase("zQ3shqwJEJyMBsBXCWyCBpUBMqxcon9oHB7mCvx4sSpMdLJwc"),
+        Ok(SigningKey::K256(_))
+    ));
+    assert!(matches!(
+        SigningKey::from_multibase("zDnaembgSGUhZULN2Caob4HLJPaxBh92N7rtH21TErzqf8HQo"),
+        Ok(SigningKey::P256(_))
+    ));
+
+    assert!(
+        SigningKey::from_multibase("Q3shqwJEJyMBsBXCWyCBpUBMqxcon9oHB7mCvx4sSpMdLJwc").is_err()
+    );
+    assert!(SigningKey::from_multibase("z111111").is_err());
+}
+
+#[cfg(test)]
+fn cid_for(data: &[u8]) -> Cid {
+    let hash = Sha256::digest(data);
+    let mh = multihash::Multihash::wrap(SHA2_256, &hash).unwrap();
+    Cid::new_v1(0x71, mh)
+}
+
+#[test]
+fn test_verify_block() {
+    let data = b"hello world";
+    let cid = cid_for(data);
+
+    assert!(verify_block(&cid, data).is_ok());
+    assert!(verify_block(&cid, b"hello w0rld").is_err());
+}
+
+#[test]
+fn test_verify_commit_signature() {
+    use k256::ecdsa::{signature::Signer, Signature};
+    use std::collections::BTreeMap;
+
+    let signing_key = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+    let key = SigningKey::K256(*signing_key.verifying_key());
+
+    let mut commit = BTreeMap::new();
+    commit.insert("did".to_string(), Ipld::String("did:plc:abc".to_string()));
+    commit.insert("version".to_string(), Ipld::Integer(3));
+    commit.insert("rev".to_string(), Ipld::String("3laaaaaaaaa22".to_string()));
+    commit.insert("data".to_string(), Ipld::Link(cid_for(b"mst root")));
+
+    let unsigned = serde_ipld_dagcbor::to_vec(&Ipld::Map(commit.clone())).unwrap();
+    let sig: Signature = signing_key.sign(&unsigned);
+    let sig = sig.normalize_s().unwrap_or(sig);
+    commit.insert("sig".to_string(), Ipld::Bytes(sig.to_bytes().to_vec()));
+    let signed = serde_ipld_dagcbor::to_vec(&Ipld::Map(commit)).unwrap();
+    let cid = cid_for(&signed);
+
+    let verified = verify_commit("did:plc:abc", cid, &signed, Some(&key)).unwrap();
+    assert_eq!(verified.rev, "3laaaaaaaaa22");
+    assert_eq!(verified.data, cid_for(b"mst root"));
+    assert!(verify_commit("did:plc:other", cid, &signed, Some(&key)).is_err());
+
+    let other = k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng);
+    let other = SigningKey::K256(*other.verifying_key());
+    assert!(verify_commit("did:plc:abc", cid, &signed, Some(&other)).is_err());
+    assert!(verify_commit("did:plc:abc", cid, &signed, None).is_ok());
+}
+
+#[test]
+fn test_car_verifier_split_input() {
+    use std::collections::BTreeMap;
+
+    let block = b"not a commit".to_vec();
+    let cid = cid_for(&block);
+
+    let mut header = BTreeMap::new();
+    header.insert("version".to_string(), Ipld::Integer(1));
+    header.insert("roots".to_string(), Ipld::List(vec![Ipld::Link(cid)]));
+    let header = serde_ipld_dagcbor::to_vec(&Ipld::Map(header)).unwrap();
+
+    let mut section = cid.to_bytes();
+    section.extend_from_slice(&block);
+
+    let mut car = vec![header.len() as u8];
+    car.extend_from_slice(&header);
+    car.push(section.len() as u8);
+    car.extend_from_slice(&section);
+
+    // byte-at-a-time should see the same blocks as all at once
+    let mut verifier = CarVerifier::new().keep_blocks();
+    for byte in &car {
+        verifier.update(std::slice::from_ref(byte)).unwrap();
+    }
+    assert!(verifier.pending.is_empty());
+    assert_eq!(verifier.commit_block.as_deref(), Some(&block[..]));
+    assert_eq!(verifier.take_blocks().len(), 1);
+
+    let mut truncated = CarVerifier::new();
+    truncated.update(&car[..car.len() - 1]).unwrap();
+    assert!(!truncated.pending.is_empty());
+
+    let mut tampered = car.clone();
+    *tampered.last_mut().unwrap() ^= 1;
+    assert!(CarVerifier::new().update(&tampered).is_err());
+
+    assert!(CarVerifier::new()
+        .with_max_bytes(car.len() as u64 - 1)
+        .update(&car)
+        .is_err());
+}
+
+#[test]
+fn test_rejects_oversized_section_length() {
+    // a section claiming to be about 2^64 bytes long
+    let mut huge = vec![0xff; 9];
+    huge.push(0x01);
+
+    assert!(CarVerifier::new().update(&huge).is_err());
+    assert!(CarVerifier::new()
+        .with_max_bytes(1024)
+        .update(&huge)
+        .is_err());
+}
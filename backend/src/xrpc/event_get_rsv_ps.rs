@@ -1,13 +1,12 @@
 use axum::{extract::State, http::StatusCode, Json};
 use jacquard_axum::ExtractXrpc;
-use jacquard_common::{
-    types::{aturi::AtUri, cid::Cid, did::Did, string::Uri},
-    CowStr,
-};
-use lex_rs::co_aktivi::event::get_rsv_ps::{GetRsvPsOutput, GetRsvPsRequest, RsvpView};
+use lex_rs::co_aktivi::event::get_rsv_ps::{GetRsvPsOutput, GetRsvPsRequest};
 use std::sync::Arc;
 
-use crate::AppState;
+use crate::{
+    hydrate::{self, RsvpRow},
+    AppState,
+};
 
 #[axum::debug_handler]
 pub async fn handle(
@@ -23,11 +22,18 @@ pub async fn handle(
         .and_then(|c| c.as_ref().parse::<i64>().ok())
         .unwrap_or(0);
 
-    let rsvps = sqlx::query!(
+    let rsvps = sqlx::query_as!(
+        RsvpRow,
         r#"
-        SELECT r.uri, r.cid, r.did, r.status, r.indexed_at
+        SELECT r.uri, r.cid, r.did, r.status, r.record, r.indexed_at, r.subject_uri, r.subject_cid,
+               e.cid AS event_cid, e.name AS event_name, e.starts_at AS event_starts_at
         FROM rsvps r
+        JOIN events e ON r.subject_uri = e.uri
         WHERE r.subject_uri = $1 AND ($2::text IS NULL OR r.status = $2)
+          AND NOT EXISTS (
+              SELECT 1 FROM accounts a
+              WHERE a.did IN (r.did, e.did) AND a.status <> 'active'
+          )
         ORDER BY r.indexed_at DESC
         LIMIT $3 OFFSET $4
         "#,
@@ -42,71 +48,9 @@ pub async fn handle(
 
     let rsvps_len = rsvps.len();
 
-    // collect unique dids to fetch profiles
-    let dids: Vec<String> = rsvps.iter().map(|r| r.did.clone()).collect();
-
-    // batch fetch profiles
-    let profiles = if !dids.is_empty() {
-        sqlx::query!(
-            r#"
-            SELECT p.did, p.display_name, p.avatar, i.handle
-            FROM profiles p
-            LEFT JOIN identities i ON p.did = i.did
-            WHERE p.did = ANY($1)
-            "#,
-            &dids
-        )
-        .fetch_all(&state.pool)
+    let rsvp_views = hydrate::event_rsvps(&state, rsvps)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    } else {
-        vec![]
-    };
-
-    // create a map for quick lookup
-    let profile_map: std::collections::HashMap<_, _> = profiles
-        .into_iter()
-        .filter_map(|p| {
-            p.did.map(|did| {
-                (
-                    did.clone(),
-                    lex_rs::co_aktivi::actor::ProfileViewBasic {
-                        did: Did::new_owned(&did).unwrap(),
-                        handle: Some(p.handle.into()),
-                        display_name: p.display_name.as_ref().map(|d| CowStr::copy_from_str(d)),
-                        avatar: p.avatar.as_ref().and_then(|a| Uri::new_owned(a).ok()),
-                        extra_data: None,
-                    },
-                )
-            })
-        })
-        .collect();
-
-    let rsvp_views: Vec<RsvpView> = rsvps
-        .into_iter()
-        .map(|rsvp| {
-            let author = profile_map.get(&rsvp.did).cloned().unwrap_or_else(|| {
-                lex_rs::co_aktivi::actor::ProfileViewBasic {
-                    did: Did::new_owned(&rsvp.did).unwrap(),
-                    handle: None,
-                    display_name: None,
-                    avatar: None,
-                    extra_data: None,
-                }
-            });
-
-            RsvpView {
-                uri: AtUri::new_owned(&rsvp.uri).unwrap(),
-                cid: Cid::cow_str(CowStr::copy_from_str(&rsvp.cid)),
-                author,
-                status: CowStr::copy_from_str(&rsvp.status),
-                indexed_at: jacquard_common::types::string::Datetime::new(
-                    rsvp.indexed_at.fixed_offset(),
-                ),
-                extra_data: None,
-            }
-        })
-        .collect();
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let cursor = if rsvps_len as i64 == limit {
         Some((offset + limit).to_string().into())
@@ -0,0 +1,185 @@
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{DateTime, Utc};
+use jacquard_axum::ExtractXrpc;
+use jacquard_common::{
+    types::{aturi::AtUri, cid::Cid, string::Datetime},
+    CowStr,
+};
+use lex_rs::co_aktivi::event::{
+    get_event_history::{GetEventHistoryOutput, GetEventHistoryRequest},
+    RevisionView,
+};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::{
+    hydrate,
+    revisions::{self, EventSnapshot},
+    AppState,
+};
+
+pub async fn handle(
+    State(state): State<Arc<AppState>>,
+    ExtractXrpc(req): ExtractXrpc<GetEventHistoryRequest>,
+) -> Result<Json<GetEventHistoryOutput<'static>>, StatusCode> {
+    let uri = req.uri.as_ref();
+
+    // only list history for events that are still indexed and visible
+    sqlx::query_scalar!(
+        r#"
+        SELECT uri
+        FROM events
+        WHERE uri = $1
+          AND NOT EXISTS (SELECT 1 FROM accounts a WHERE a.did = events.did AND a.status <> 'active')
+        "#,
+        uri
+    )
+    .fetch_optional(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let limit = req.limit.unwrap_or(50).clamp(1, 100);
+    let after = req
+        .cursor
+        .as_ref()
+        .map(|c| parse_cursor(c.as_ref()).ok_or(StatusCode::BAD_REQUEST))
+        .transpose()?;
+    let (after_rev, after_cid) = after.unzip();
+
+    let rows = sqlx::query_as!(
+        RevisionRow,
+        r#"
+        SELECT rev, cid, name, description, starts_at, ends_at, mode, status, locations, uris, record, indexed_at
+        FROM event_revisions
+        WHERE uri = $1
+          AND ($2::text IS NULL OR (COALESCE(rev, ''), cid) > ($2, $3::text))
+        ORDER BY COALESCE(rev, '') ASC, cid ASC
+        LIMIT $4
+        "#,
+        uri,
+        after_rev,
+        after_cid,
+        limit
+    )
+    .fetch_all(&state.pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // the first revision on a later page is diffed against the last one the
+    // cursor points past
+    let mut previous = match (after_rev.as_deref(), after_cid.as_deref()) {
+        (Some(rev), Some(cid)) => sqlx::query_as!(
+            RevisionRow,
+            r#"
+            SELECT rev, cid, name, description, starts_at, ends_at, mode, status, locations, uris, record, indexed_at
+            FROM event_revisions
+            WHERE uri = $1 AND (COALESCE(rev, ''), cid) <= ($2, $3)
+            ORDER BY COALESCE(rev, '') DESC, cid DESC
+            LIMIT 1
+            "#,
+            uri,
+            rev,
+            cid
+        )
+        .fetch_optional(&state.pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map(RevisionRow::snapshot),
+        _ => None,
+    };
+
+    let cursor = if rows.len() as i64 == limit {
+        rows.last()
+            .map(|row| format!("{}_{}", row.rev.as_deref().unwrap_or_default(), row.cid).into())
+    } else {
+        None
+    };
+
+    let mut revisions = Vec::with_capacity(rows.len());
+    for mut row in rows {
+        let cid = std::mem::take(&mut row.cid);
+        let record = row.record.take();
+        let indexed_at = row.indexed_at;
+        let snapshot = row.snapshot();
+        let changes = previous
+            .as_ref()
+            .map(|prev| revisions::diff(prev, &snapshot))
+            .unwrap_or_default();
+
+        // revisions from before raw records were kept only have the tracked fields
+        let record = record.unwrap_or_else(|| snapshot.to_json());
+
+        revisions.push(RevisionView {
+            cid: Cid::cow_str(CowStr::copy_from_str(&cid)),
+            record: hydrate::record_data(record, uri)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+            changes,
+            indexed_at: Datetime::new(indexed_at.fixed_offset()),
+            extra_data: None,
+        });
+        previous = Some(snapshot);
+    }
+
+    Ok(Json(GetEventHistoryOutput {
+        cursor,
+        uri: AtUri::new_owned(uri).unwrap(),
+        revisions,
+        extra_data: None,
+    }))
+}
+
+struct RevisionRow {
+    rev: Option<String>,
+    cid: String,
+    name: String,
+    description: Option<String>,
+    starts_at: Option<DateTime<Utc>>,
+    ends_at: Option<DateTime<Utc>>,
+    mode: Option<String>,
+    status: Option<String>,
+    locations: Option<Value>,
+    uris: Option<Value>,
+    record: Option<Value>,
+    indexed_at: DateTime<Utc>,
+}
+
+impl RevisionRow {
+    fn snapshot(self) -> EventSnapshot {
+        EventSnapshot {
+            name: self.name,
+            description: self.description,
+            starts_at: self.starts_at,
+            ends_at: self.ends_at,
+            mode: self.mode,
+            status: self.status,
+            locations: self.locations,
+            uris: self.uris,
+        }
+    }
+}
+
+/// Split a `{rev}_{cid}` cursor into the revision it points at. Revisions
+/// indexed before revs were kept have an empty rev, and sort first
+fn parse_cursor(cursor: &str) -> Option<(String, String)> {
+    let (rev, cid) = cursor.split_once('_')?;
+    if cid.is_empty() || !rev.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    Some((rev.to_string(), cid.to_string()))
+}
+
+#[test]
+fn test_parse_cursor() {
+    let (rev, cid) = parse_cursor("3l3qo2vuowo2b_bafyreiabc").unwrap();
+    assert_eq!(rev, "3l3qo2vuowo2b");
+    assert_eq!(cid, "bafyreiabc");
+
+    let (rev, cid) = parse_cursor("_bafyreiabc").unwrap();
+    assert_eq!(rev, "");
+    assert_eq!(cid, "bafyreiabc");
+
+    assert!(parse_cursor("50").is_none());
+    assert!(parse_cursor("3l3q-o2_bafyreiabc").is_none());
+    assert!(parse_cursor("3l3qo2vuowo2b_").is_none());
+}
@@ -1,19 +1,12 @@
 use axum::{extract::State, http::StatusCode, Json};
 use jacquard_axum::ExtractXrpc;
-use jacquard_common::{
-    types::{aturi::AtUri, cid::Cid, did::Did},
-    CowStr, Data,
-};
-use lex_rs::co_aktivi::{
-    actor::{
-        get_rsv_ps::{GetRsvPsOutput, GetRsvPsRequest},
-        ProfileViewBasic,
-    },
-    event::EventViewBasic,
-};
+use lex_rs::co_aktivi::actor::get_rsv_ps::{GetRsvPsOutput, GetRsvPsRequest};
 use std::sync::Arc;
 
-use crate::AppState;
+use crate::{
+    hydrate::{self, RsvpRow},
+    AppState,
+};
 
 #[axum::debug_handler]
 pub async fn handle(
@@ -32,13 +25,18 @@ pub async fn handle(
     // for now assume it's a DID, but we should add handle resolution
     let did = actor;
 
-    let rsvps = sqlx::query!(
+    let rsvps = sqlx::query_as!(
+        RsvpRow,
         r#"
-        SELECT r.uri, r.cid, r.did, r.subject_uri, r.subject_cid, r.status, r.indexed_at,
-               e.name as event_name, e.starts_at as event_starts_at
+        SELECT r.uri, r.cid, r.did, r.status, r.record, r.indexed_at, r.subject_uri, r.subject_cid,
+               e.cid AS event_cid, e.name AS event_name, e.starts_at AS event_starts_at
         FROM rsvps r
         JOIN events e ON r.subject_uri = e.uri
         WHERE r.did = $1
+          AND NOT EXISTS (
+              SELECT 1 FROM accounts a
+              WHERE a.did IN (r.did, e.did) AND a.status <> 'active'
+          )
         ORDER BY r.indexed_at DESC
         LIMIT $2 OFFSET $3
         "#,
@@ -51,41 +49,10 @@ pub async fn handle(
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let rsvps_len = rsvps.len();
-    let rsvp_views = rsvps
-        .into_iter()
-        .map(|rsvp| lex_rs::co_aktivi::actor::get_rsv_ps::RsvpView {
-            uri: AtUri::new_owned(&rsvp.uri).unwrap(),
-            cid: Cid::cow_str(CowStr::copy_from_str(&rsvp.cid)),
-            author: ProfileViewBasic {
-                did: Did::new_owned(did).unwrap(),
-                handle: None,
-                display_name: None,
-                avatar: None,
-                extra_data: None,
-            },
-            record: Data::from_json_owned(serde_json::json!({
-                "subject": {
-                    "uri": rsvp.subject_uri,
-                    "cid": rsvp.subject_cid,
-                },
-                "status": rsvp.status,
-            }))
-            .unwrap(),
-            event: EventViewBasic {
-                uri: AtUri::new_owned(&rsvp.subject_uri).unwrap(),
-                cid: Cid::cow_str(CowStr::copy_from_str(&rsvp.subject_cid)),
-                name: CowStr::copy_from_str(&rsvp.event_name),
-                starts_at: rsvp
-                    .event_starts_at
-                    .map(|dt| jacquard_common::types::string::Datetime::new(dt.fixed_offset())),
-                extra_data: None,
-            },
-            indexed_at: jacquard_common::types::string::Datetime::new(
-                rsvp.indexed_at.fixed_offset(),
-            ),
-            extra_data: None,
-        })
-        .collect();
+
+    let rsvp_views = hydrate::actor_rsvps(&state, rsvps)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let cursor = if rsvps_len as i64 == limit {
         Some((offset + limit).to_string().into())
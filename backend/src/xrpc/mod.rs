@@ -1,6 +1,7 @@
 pub mod actor_get_events;
 pub mod actor_get_timeline;
 pub mod event_get_rsv_ps;
+pub mod get_event_history;
 pub mod get_event_view;
 pub mod get_events;
 pub mod get_profile_view;
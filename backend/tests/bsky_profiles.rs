@@ -0,0 +1,194 @@
+//! Bluesky profile lookups and the refresh worker against a local stand-in
+//! AppView. The worker tests need a database; run with `DATABASE_URL` set.
+
+use aktivi::{
+    profile::{self, BskyProfiles},
+    sink::{
+        Applied, RawRecord, RecordKind, RecordOp, RecordSink, BSKY_PROFILE_COLLECTION,
+        EVENT_COLLECTION,
+    },
+};
+use axum::{extract::RawQuery, routing::get, Json, Router};
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+
+/// An AppView that knows every DID except `did:plc:unknown`, and records the
+/// actors asked for in each `getProfiles` call
+async fn stand_in_appview() -> (String, Arc<Mutex<Vec<Vec<String>>>>) {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recorded = calls.clone();
+    let app = Router::new().route(
+        "/xrpc/app.bsky.actor.getProfiles",
+        get(move |RawQuery(query): RawQuery| {
+            let actors: Vec<String> = query
+                .unwrap_or_default()
+                .split('&')
+                .filter_map(|pair| pair.strip_prefix("actors="))
+                .map(|did| did.replace("%3A", ":"))
+                .collect();
+            recorded.lock().unwrap().push(actors.clone());
+
+            let profiles: Vec<_> = actors
+                .iter()
+                .filter(|did| *did != "did:plc:unknown")
+                .map(|did| {
+                    serde_json::json!({
+                        "did": did,
+                        "handle": "handle.invalid",
+                        "displayName": format!("name of {}", did),
+                        "avatar": format!("https://cdn.bsky.app/img/avatar/plain/{}/bafkreiavatar@jpeg", did),
+                    })
+                })
+                .collect();
+            async move { Json(serde_json::json!({ "profiles": profiles })) }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), calls)
+}
+
+fn dids(range: std::ops::Range<usize>) -> Vec<String> {
+    range.map(|n| format!("did:plc:user{}", n)).collect()
+}
+
+#[tokio::test]
+async fn keeps_blob_cids_from_cdn_urls() {
+    let (url, calls) = stand_in_appview().await;
+    let bsky = BskyProfiles::new(url);
+
+    let mut batch = dids(0..3);
+    batch.push("did:plc:unknown".to_string());
+    let found = bsky.fetch_batch(&batch).await.unwrap();
+
+    assert_eq!(found.len(), 3);
+    let user = &found["did:plc:user1"];
+    assert_eq!(user.display_name.as_deref(), Some("name of did:plc:user1"));
+    assert_eq!(user.handle, None);
+    assert_eq!(user.avatar_cid.as_deref(), Some("bafkreiavatar"));
+    assert_eq!(calls.lock().unwrap().len(), 1);
+}
+
+async fn host_event(pool: &PgPool, did: &str) {
+    RecordSink::new(pool.clone())
+        .apply(RecordOp {
+            did,
+            collection: EVENT_COLLECTION,
+            rkey: "3laaaaaaaaa21",
+            cid: Some("bafyreipicnic"),
+            rev: Some("3lbbbbbbb0001"),
+            record: Some(RawRecord::Json(serde_json::json!({
+                "$type": EVENT_COLLECTION,
+                "name": "Picnic",
+                "createdAt": "2025-01-01T00:00:00.000Z",
+            }))),
+        })
+        .await
+        .unwrap();
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn stores_profiles_of_event_hosts(pool: PgPool) {
+    let (url, calls) = stand_in_appview().await;
+    let bsky = BskyProfiles::new(url);
+    let mut hosts = dids(0..30);
+    hosts.push("did:plc:unknown".to_string());
+    for did in &hosts {
+        host_event(&pool, did).await;
+    }
+
+    assert_eq!(profile::refresh_due(&pool, &bsky).await.unwrap(), (31, 0));
+    let sizes: Vec<usize> = calls.lock().unwrap().iter().map(|c| c.len()).collect();
+    assert_eq!(sizes, vec![25, 6]);
+
+    let stored = sqlx::query!(
+        "SELECT source, display_name, avatar_cid FROM bsky_profiles WHERE did = 'did:plc:user7'"
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stored.source, "appview");
+    assert_eq!(
+        stored.display_name.as_deref(),
+        Some("name of did:plc:user7")
+    );
+    assert_eq!(stored.avatar_cid.as_deref(), Some("bafkreiavatar"));
+
+    // unknown accounts are stored empty rather than asked for every pass
+    let unknown =
+        sqlx::query_scalar!("SELECT display_name FROM bsky_profiles WHERE did = 'did:plc:unknown'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    assert_eq!(unknown, None);
+
+    // nothing is due until a profile goes stale
+    assert_eq!(profile::refresh_due(&pool, &bsky).await.unwrap(), (0, 0));
+    profile::mark_stale(&pool, "did:plc:user3").await.unwrap();
+    assert_eq!(profile::refresh_due(&pool, &bsky).await.unwrap(), (1, 0));
+    assert_eq!(
+        calls.lock().unwrap().last().unwrap(),
+        &vec!["did:plc:user3".to_string()]
+    );
+}
+
+fn bsky_profile_op<'a>(did: &'a str, rev: &'a str, name: Option<&str>) -> RecordOp<'a> {
+    RecordOp {
+        did,
+        collection: BSKY_PROFILE_COLLECTION,
+        rkey: "self",
+        cid: Some("bafyreiprofile"),
+        rev: Some(rev),
+        record: name.map(|name| {
+            RawRecord::Json(serde_json::json!({
+                "$type": BSKY_PROFILE_COLLECTION,
+                "displayName": name,
+            }))
+        }),
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn profile_records_win_over_the_appview(pool: PgPool) {
+    let (url, _) = stand_in_appview().await;
+    let bsky = BskyProfiles::new(url);
+    let sink = RecordSink::new(pool.clone());
+    let did = "did:plc:user1";
+    host_event(&pool, did).await;
+
+    let applied = sink
+        .apply(bsky_profile_op(did, "3lbbbbbbb0002", Some("from record")))
+        .await
+        .unwrap();
+    assert_eq!(applied, Applied::Upserted(RecordKind::BskyProfile));
+    let older = sink
+        .apply(bsky_profile_op(did, "3lbbbbbbb0001", Some("older")))
+        .await
+        .unwrap();
+    assert_eq!(older, Applied::Outdated(RecordKind::BskyProfile));
+
+    // the AppView's copy doesn't replace the record
+    assert_eq!(profile::refresh_due(&pool, &bsky).await.unwrap(), (1, 0));
+    let stored = sqlx::query!(
+        "SELECT source, display_name FROM bsky_profiles WHERE did = $1",
+        did
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(stored.source, "record");
+    assert_eq!(stored.display_name.as_deref(), Some("from record"));
+
+    let deleted = sink
+        .apply(bsky_profile_op(did, "3lbbbbbbb0003", None))
+        .await
+        .unwrap();
+    assert_eq!(deleted, Applied::Deleted(RecordKind::BskyProfile));
+    let name = sqlx::query_scalar!("SELECT display_name FROM bsky_profiles WHERE did = $1", did)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(name, None);
+}
@@ -0,0 +1,99 @@
+//! Records the sink rejects are kept in `failed_records` until a replay or a
+//! later version of the record gets them indexed.
+
+use aktivi::{
+    failed_records,
+    sink::{Applied, RawRecord, RecordKind, RecordOp, RecordSink, EVENT_COLLECTION},
+};
+use sqlx::PgPool;
+
+const DID: &str = "did:plc:failedrecordtest";
+
+fn op(rkey: &'static str, rev: &'static str, record: serde_json::Value) -> RecordOp<'static> {
+    RecordOp {
+        did: DID,
+        collection: EVENT_COLLECTION,
+        rkey,
+        cid: Some("bafyreifailedrecordtest"),
+        rev: Some(rev),
+        record: Some(RawRecord::Json(record)),
+    }
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn stores_and_replays_failed_records(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+
+    // missing the required name
+    let invalid = serde_json::json!({
+        "$type": EVENT_COLLECTION,
+        "createdAt": "2025-01-01T00:00:00.000Z",
+    });
+    assert!(sink
+        .apply(op("3laaaaaaaaa21", "3lbbbbbbbbb21", invalid.clone()))
+        .await
+        .is_err());
+    assert!(sink
+        .apply(op("3laaaaaaaaa22", "3lbbbbbbbbb22", invalid.clone()))
+        .await
+        .is_err());
+
+    let failed = failed_records::list(&pool, Some(EVENT_COLLECTION), 10)
+        .await
+        .unwrap();
+    assert_eq!(failed.len(), 2);
+    let uri = format!("at://{}/{}/3laaaaaaaaa21", DID, EVENT_COLLECTION);
+    let stored = failed_records::get(&pool, &uri).await.unwrap().unwrap();
+    assert_eq!(stored.encoding.as_deref(), Some("json"));
+    assert_eq!(stored.record_json().unwrap(), Some(invalid));
+    assert!(stored.error.contains("failed to parse event"));
+
+    // still broken, so the replay keeps it and counts another failure
+    let summary = failed_records::replay(&pool, Some(&uri), None)
+        .await
+        .unwrap();
+    assert_eq!((summary.replayed, summary.failed), (0, 1));
+    let stored = failed_records::get(&pool, &uri).await.unwrap().unwrap();
+    assert_eq!(stored.failures, 2);
+
+    // a newer, valid version of the record supersedes the failure
+    let fixed = serde_json::json!({
+        "$type": EVENT_COLLECTION,
+        "name": "fixed",
+        "createdAt": "2025-01-01T00:00:00.000Z",
+    });
+    assert_eq!(
+        sink.apply(op("3laaaaaaaaa21", "3lbbbbbbbbb23", fixed))
+            .await
+            .unwrap(),
+        Applied::Upserted(RecordKind::Event)
+    );
+    assert!(failed_records::get(&pool, &uri).await.unwrap().is_none());
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn older_failure_keeps_newer_one(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+    let invalid = |name: &str| {
+        serde_json::json!({
+            "$type": EVENT_COLLECTION,
+            "name": name,
+        })
+    };
+
+    // live ingestion fails on the edit before a backfill fails on the original
+    assert!(sink
+        .apply(op("3laaaaaaaaa21", "3lbbbbbbbbb22", invalid("edited")))
+        .await
+        .is_err());
+    assert!(sink
+        .apply(op("3laaaaaaaaa21", "3lbbbbbbbbb21", invalid("original")))
+        .await
+        .is_err());
+
+    let uri = format!("at://{}/{}/3laaaaaaaaa21", DID, EVENT_COLLECTION);
+    let stored = failed_records::get(&pool, &uri).await.unwrap().unwrap();
+    assert_eq!(stored.rev.as_deref(), Some("3lbbbbbbbbb22"));
+    assert_eq!(stored.record_json().unwrap(), Some(invalid("edited")));
+    assert_eq!(stored.failures, 1);
+}
@@ -0,0 +1,7 @@
+//! Tests against a database. `sqlx::test` gives each one a fresh database
+//! with the migrations applied, so `DATABASE_URL` has to be set, as for the
+//! rest of the build.
+
+mod failed_records;
+mod out_of_order;
+mod reindex;
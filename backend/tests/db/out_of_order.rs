@@ -0,0 +1,183 @@
+//! Writes of the same record racing in from live ingestion and backfill, in
+//! any order, must leave the newest version indexed.
+
+use aktivi::sink::{self, Applied, RawRecord, RecordKind, RecordOp, RecordSink, EVENT_COLLECTION};
+use rand::seq::SliceRandom;
+use sqlx::PgPool;
+use std::time::Duration;
+
+const DID: &str = "did:plc:outofordertest";
+const RKEY: &str = "3laaaaaaaaa21";
+
+fn rev(n: usize) -> String {
+    format!("3lbbbbbbb{:04}", n)
+}
+
+fn event(name: &str) -> RawRecord<'static> {
+    RawRecord::Json(serde_json::json!({
+        "$type": EVENT_COLLECTION,
+        "name": name,
+        "createdAt": "2025-01-01T00:00:00.000Z",
+    }))
+}
+
+async fn write(sink: &RecordSink, rev: &str, name: Option<&str>) -> Applied {
+    sink.apply(RecordOp {
+        did: DID,
+        collection: EVENT_COLLECTION,
+        rkey: RKEY,
+        cid: Some(&format!("bafyrei{}", rev)),
+        rev: Some(rev),
+        record: name.map(event),
+    })
+    .await
+    .unwrap()
+}
+
+async fn indexed(pool: &PgPool) -> Option<(String, Option<String>)> {
+    sqlx::query!(
+        "SELECT name, rev FROM events WHERE did = $1 AND rkey = $2",
+        DID,
+        RKEY
+    )
+    .fetch_optional(pool)
+    .await
+    .unwrap()
+    .map(|row| (row.name, row.rev))
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn newest_write_wins_racing_writers(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+    let mut revs: Vec<usize> = (0..32).collect();
+    revs.shuffle(&mut rand::thread_rng());
+
+    let writers = revs.into_iter().map(|n| {
+        let sink = sink.clone();
+        tokio::spawn(async move {
+            let rev = rev(n);
+            write(&sink, &rev, Some(&format!("version {}", n))).await
+        })
+    });
+    for writer in futures::future::join_all(writers).await {
+        writer.unwrap();
+    }
+
+    assert_eq!(
+        indexed(&pool).await,
+        Some(("version 31".to_string(), Some(rev(31))))
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn backfill_does_not_overwrite_newer_live_write(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+
+    // jetstream delivers the edit before a backfill of the older repo lands
+    assert_eq!(
+        write(&sink, &rev(2), Some("edited")).await,
+        Applied::Upserted(RecordKind::Event)
+    );
+    assert_eq!(
+        write(&sink, &rev(1), Some("original")).await,
+        Applied::Outdated(RecordKind::Event)
+    );
+    assert_eq!(
+        indexed(&pool).await,
+        Some(("edited".to_string(), Some(rev(2))))
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn delete_is_not_undone_by_older_write(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+
+    write(&sink, &rev(1), Some("original")).await;
+    assert_eq!(
+        write(&sink, &rev(3), None).await,
+        Applied::Deleted(RecordKind::Event)
+    );
+
+    // a backfill from before the delete
+    assert_eq!(
+        write(&sink, &rev(2), Some("original")).await,
+        Applied::Outdated(RecordKind::Event)
+    );
+    assert_eq!(indexed(&pool).await, None);
+
+    // recreated after the delete
+    assert_eq!(
+        write(&sink, &rev(4), Some("recreated")).await,
+        Applied::Upserted(RecordKind::Event)
+    );
+    assert_eq!(
+        indexed(&pool).await,
+        Some(("recreated".to_string(), Some(rev(4))))
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn older_delete_keeps_newer_write(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+
+    write(&sink, &rev(5), Some("current")).await;
+    assert_eq!(write(&sink, &rev(4), None).await, Applied::Ignored);
+    assert_eq!(
+        indexed(&pool).await,
+        Some(("current".to_string(), Some(rev(5))))
+    );
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn delete_waits_for_racing_backfill_write(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+
+    // a backfill is writing the record, but hasn't committed when live
+    // ingestion sees it deleted
+    let mut backfill = pool.begin().await.unwrap();
+    let rev1 = rev(1);
+    let applied = sink::apply(
+        &mut backfill,
+        &RecordOp {
+            did: DID,
+            collection: EVENT_COLLECTION,
+            rkey: RKEY,
+            cid: Some("bafyreibackfill"),
+            rev: Some(&rev1),
+            record: Some(event("original")),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(applied, Applied::Upserted(RecordKind::Event));
+
+    let delete = tokio::spawn({
+        let sink = sink.clone();
+        async move { write(&sink, &rev(2), None).await }
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(!delete.is_finished(), "delete should wait for the backfill");
+
+    backfill.commit().await.unwrap();
+    assert_eq!(delete.await.unwrap(), Applied::Deleted(RecordKind::Event));
+    assert_eq!(indexed(&pool).await, None);
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn same_rev_write_does_not_replace_row(pool: PgPool) {
+    let sink = RecordSink::new(pool.clone());
+
+    // a backfill replaying the commit live ingestion already indexed
+    assert_eq!(
+        write(&sink, &rev(1), Some("original")).await,
+        Applied::Upserted(RecordKind::Event)
+    );
+    assert_eq!(
+        write(&sink, &rev(1), Some("replayed")).await,
+        Applied::Outdated(RecordKind::Event)
+    );
+    assert_eq!(
+        indexed(&pool).await,
+        Some(("original".to_string(), Some(rev(1))))
+    );
+}
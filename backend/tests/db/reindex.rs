@@ -0,0 +1,59 @@
+//! The raw record is kept as written, and `reindex` rebuilds the derived
+//! columns from it.
+
+use aktivi::{
+    reindex,
+    sink::{Applied, RawRecord, RecordKind, RecordOp, RecordSink, EVENT_COLLECTION},
+};
+use sqlx::PgPool;
+
+const DID: &str = "did:plc:reindextest";
+const RKEY: &str = "3laaaaaaaaa21";
+
+#[sqlx::test(migrations = "./migrations")]
+async fn reindex_rebuilds_columns_from_raw_record(pool: PgPool) {
+    let record = serde_json::json!({
+        "$type": EVENT_COLLECTION,
+        "name": "Picnic",
+        "createdAt": "2025-01-01T00:00:00.000Z",
+        "com.example.extension": { "bring": "blankets" },
+    });
+    let applied = RecordSink::new(pool.clone())
+        .apply(RecordOp {
+            did: DID,
+            collection: EVENT_COLLECTION,
+            rkey: RKEY,
+            cid: Some("bafyreipicnic"),
+            rev: Some("3lbbbbbbb0001"),
+            record: Some(RawRecord::Json(record.clone())),
+        })
+        .await
+        .unwrap();
+    assert_eq!(applied, Applied::Upserted(RecordKind::Event));
+
+    // fields we don't know about are kept verbatim
+    let stored = sqlx::query_scalar!("SELECT record FROM events WHERE did = $1", DID)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(stored, record);
+
+    sqlx::query!("UPDATE events SET name = 'stale' WHERE did = $1", DID)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let summary = reindex::reindex(&pool, Some(EVENT_COLLECTION))
+        .await
+        .unwrap();
+    assert_eq!(summary.reindexed, 1);
+    assert_eq!(summary.quarantined, 0);
+    assert_eq!(summary.failed, 0);
+
+    let row = sqlx::query!("SELECT name, rev FROM events WHERE did = $1", DID)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(row.name, "Picnic");
+    assert_eq!(row.rev.as_deref(), Some("3lbbbbbbb0001"));
+}
@@ -0,0 +1,410 @@
+//! Replays a recorded sequence of `subscribeRepos` frames from a local
+//! websocket stand-in and checks what the firehose consumer decodes and
+//! indexes. The ingestion test needs a database; run with `DATABASE_URL` set.
+
+use aktivi::firehose::{decode_frame, load_cursor, subscribe, FirehoseConsumer};
+use futures::{SinkExt, StreamExt};
+use ipld_core::cid::Cid;
+use rocketman::types::event::{AccountStatus, Operation};
+use serde::Serialize;
+use serde_bytes::ByteBuf;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+
+const DID: &str = "did:plc:replaytestaccount";
+
+#[derive(Serialize)]
+struct Header<'a> {
+    op: i64,
+    t: &'a str,
+}
+
+#[derive(Serialize)]
+struct CarHeader {
+    roots: Vec<Cid>,
+    version: u64,
+}
+
+#[derive(Serialize)]
+struct Op<'a> {
+    action: &'a str,
+    path: &'a str,
+    cid: Option<Cid>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Commit<'a> {
+    seq: u64,
+    repo: &'a str,
+    rev: &'a str,
+    since: Option<&'a str>,
+    blocks: ByteBuf,
+    ops: Vec<Op<'a>>,
+    blobs: Vec<Cid>,
+    time: &'a str,
+    too_big: bool,
+    rebase: bool,
+}
+
+#[derive(Serialize)]
+struct Identity<'a> {
+    seq: u64,
+    did: &'a str,
+    time: &'a str,
+    handle: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct Account<'a> {
+    seq: u64,
+    did: &'a str,
+    time: &'a str,
+    active: bool,
+    status: Option<&'a str>,
+}
+
+/// A repo commit; the relay checks the signature, so any bytes do here
+#[derive(Serialize)]
+struct RepoCommit<'a> {
+    did: &'a str,
+    version: u64,
+    data: Cid,
+    rev: &'a str,
+    prev: Option<Cid>,
+    sig: ByteBuf,
+}
+
+#[derive(Serialize)]
+struct Info<'a> {
+    name: &'a str,
+}
+
+fn cid_for(block: &[u8]) -> Cid {
+    let hash = Sha256::digest(block);
+    let mh = multihash::Multihash::wrap(0x12, &hash).unwrap();
+    Cid::new_v1(0x71, mh)
+}
+
+fn varint(mut n: usize, out: &mut Vec<u8>) {
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn car(blocks: &[(Cid, Vec<u8>)]) -> Vec<u8> {
+    let header = serde_ipld_dagcbor::to_vec(&CarHeader {
+        roots: blocks.iter().map(|(cid, _)| *cid).take(1).collect(),
+        version: 1,
+    })
+    .unwrap();
+
+    let mut out = Vec::new();
+    varint(header.len(), &mut out);
+    out.extend_from_slice(&header);
+
+    for (cid, data) in blocks {
+        let cid_bytes = cid.to_bytes();
+        varint(cid_bytes.len() + data.len(), &mut out);
+        out.extend_from_slice(&cid_bytes);
+        out.extend_from_slice(data);
+    }
+
+    out
+}
+
+/// A commit block for `rev`, to root a frame's CAR slice at
+fn commit_block(rev: &str) -> (Cid, Vec<u8>) {
+    let block = serde_ipld_dagcbor::to_vec(&RepoCommit {
+        did: DID,
+        version: 3,
+        data: cid_for(b"mst root"),
+        rev,
+        prev: None,
+        sig: ByteBuf::from(vec![0; 64]),
+    })
+    .unwrap();
+    (cid_for(&block), block)
+}
+
+fn frame<T: Serialize>(t: &str, body: &T) -> Vec<u8> {
+    let mut out = serde_ipld_dagcbor::to_vec(&Header { op: 1, t }).unwrap();
+    out.extend(serde_ipld_dagcbor::to_vec(body).unwrap());
+    out
+}
+
+/// The recorded stream: an event create alongside an unrelated post, an rsvp
+/// delete, a handle change, a deactivation and an info frame we don't index.
+fn recorded_stream() -> Vec<Vec<u8>> {
+    let event = serde_ipld_dagcbor::to_vec(&serde_json::json!({
+        "$type": "community.lexicon.calendar.event",
+        "name": "Replay Meetup",
+        "createdAt": "2025-01-01T00:00:00.000Z",
+        "startsAt": "2025-02-01T18:00:00.000Z",
+    }))
+    .unwrap();
+    let event_cid = cid_for(&event);
+
+    let post = serde_ipld_dagcbor::to_vec(&serde_json::json!({
+        "$type": "app.bsky.feed.post",
+        "text": "not indexed",
+        "createdAt": "2025-01-01T00:00:00.000Z",
+    }))
+    .unwrap();
+    let post_cid = cid_for(&post);
+
+    vec![
+        frame(
+            "#commit",
+            &Commit {
+                seq: 100,
+                repo: DID,
+                rev: "3laaaaaaaaa22",
+                since: None,
+                blocks: ByteBuf::from(car(&[
+                    commit_block("3laaaaaaaaa22"),
+                    (event_cid, event),
+                    (post_cid, post),
+                ])),
+                ops: vec![
+                    Op {
+                        action: "create",
+                        path: "community.lexicon.calendar.event/3laaaaaaaaa21",
+                        cid: Some(event_cid),
+                    },
+                    Op {
+                        action: "create",
+                        path: "app.bsky.feed.post/3laaaaaaaaa21",
+                        cid: Some(post_cid),
+                    },
+                ],
+                blobs: vec![],
+                time: "2025-01-01T00:00:01.000Z",
+                too_big: false,
+                rebase: false,
+            },
+        ),
+        frame(
+            "#commit",
+            &Commit {
+                seq: 101,
+                repo: DID,
+                rev: "3laaaaaaaaa23",
+                since: Some("3laaaaaaaaa22"),
+                blocks: ByteBuf::from(car(&[commit_block("3laaaaaaaaa23")])),
+                ops: vec![Op {
+                    action: "delete",
+                    path: "community.lexicon.calendar.rsvp/3laaaaaaaaa20",
+                    cid: None,
+                }],
+                blobs: vec![],
+                time: "2025-01-01T00:00:02.000Z",
+                too_big: false,
+                rebase: false,
+            },
+        ),
+        frame(
+            "#identity",
+            &Identity {
+                seq: 102,
+                did: DID,
+                time: "2025-01-01T00:00:03.000Z",
+                handle: Some("replay.example.com"),
+            },
+        ),
+        frame(
+            "#account",
+            &Account {
+                seq: 103,
+                did: DID,
+                time: "2025-01-01T00:00:04.000Z",
+                active: false,
+                status: Some("deactivated"),
+            },
+        ),
+        frame(
+            "#info",
+            &Info {
+                name: "OutdatedCursor",
+            },
+        ),
+    ]
+}
+
+/// Serve the recorded frames once over a local websocket and return its url
+async fn serve(frames: Vec<Vec<u8>>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+        for frame in frames {
+            ws.send(Message::Binary(frame.into())).await.unwrap();
+        }
+        ws.close(None).await.ok();
+    });
+
+    format!("ws://{}", addr)
+}
+
+#[tokio::test]
+async fn replays_recorded_stream() {
+    let url = serve(recorded_stream()).await;
+    let frames: Vec<Vec<u8>> = subscribe(&url, None)
+        .await
+        .unwrap()
+        .map(|f| f.unwrap())
+        .collect()
+        .await;
+    assert_eq!(frames.len(), 5);
+
+    // event create, unrelated post filtered out
+    let decoded = decode_frame(&frames[0]).unwrap().unwrap();
+    assert_eq!(decoded.seq, 100);
+    assert_eq!(decoded.events.len(), 1);
+    let event = &decoded.events[0];
+    assert_eq!(event.did, DID);
+    let commit = event.commit.as_ref().unwrap();
+    assert!(matches!(commit.operation, Operation::Create));
+    assert_eq!(commit.collection, "community.lexicon.calendar.event");
+    assert_eq!(commit.rkey, "3laaaaaaaaa21");
+    let record = commit.record.as_ref().unwrap();
+    assert_eq!(record["name"], "Replay Meetup");
+    assert_eq!(record["startsAt"], "2025-02-01T18:00:00.000Z");
+
+    // rsvp delete carries no record
+    let decoded = decode_frame(&frames[1]).unwrap().unwrap();
+    assert_eq!(decoded.seq, 101);
+    let commit = decoded.events[0].commit.as_ref().unwrap();
+    assert!(matches!(commit.operation, Operation::Delete));
+    assert_eq!(commit.collection, "community.lexicon.calendar.rsvp");
+    assert!(commit.record.is_none());
+
+    // identity frames become identity events
+    let decoded = decode_frame(&frames[2]).unwrap().unwrap();
+    assert_eq!(decoded.seq, 102);
+    let identity = decoded.events[0].identity.as_ref().unwrap();
+    assert_eq!(identity.handle.as_deref(), Some("replay.example.com"));
+
+    // account frames become account events
+    let decoded = decode_frame(&frames[3]).unwrap().unwrap();
+    assert_eq!(decoded.seq, 103);
+    let account = decoded.events[0].account.as_ref().unwrap();
+    assert_eq!(account.did, DID);
+    assert!(matches!(account.status, Some(AccountStatus::Deactivated)));
+
+    // frame types we don't index are skipped
+    assert!(decode_frame(&frames[4]).unwrap().is_none());
+}
+
+#[sqlx::test(migrations = "./migrations")]
+async fn indexes_recorded_stream(pool: PgPool) {
+    let url = serve(recorded_stream()).await;
+    let consumer = FirehoseConsumer::new(url, pool.clone());
+    // returns once the stand-in closes the stream
+    assert!(consumer.consume().await.is_err());
+
+    let name = sqlx::query_scalar!("SELECT name FROM events WHERE did = $1", DID)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(name, "Replay Meetup");
+
+    let status = sqlx::query_scalar!("SELECT status FROM accounts WHERE did = $1", DID)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(status, "deactivated");
+
+    assert_eq!(load_cursor(&pool).await.unwrap(), Some(103));
+}
+
+#[test]
+fn rejects_truncated_car() {
+    let event = serde_ipld_dagcbor::to_vec(&serde_json::json!({ "name": "x" })).unwrap();
+    let event_cid = cid_for(&event);
+    let mut blocks = car(&[commit_block("3laaaaaaaaa22"), (event_cid, event)]);
+    blocks.truncate(blocks.len() - 4);
+
+    let bytes = frame(
+        "#commit",
+        &Commit {
+            seq: 1,
+            repo: DID,
+            rev: "3laaaaaaaaa22",
+            since: None,
+            blocks: ByteBuf::from(blocks),
+            ops: vec![Op {
+                action: "create",
+                path: "community.lexicon.calendar.event/3laaaaaaaaa21",
+                cid: Some(event_cid),
+            }],
+            blobs: vec![],
+            time: "2025-01-01T00:00:01.000Z",
+            too_big: false,
+            rebase: false,
+        },
+    );
+
+    assert!(decode_frame(&bytes).is_err());
+}
+
+#[test]
+fn skips_malformed_record_in_commit() {
+    let event = serde_ipld_dagcbor::to_vec(&serde_json::json!({
+        "$type": "community.lexicon.calendar.event",
+        "name": "Still Indexed",
+        "createdAt": "2025-01-01T00:00:00.000Z",
+    }))
+    .unwrap();
+    let event_cid = cid_for(&event);
+    // hashes to its cid, but isn't dag-cbor
+    let broken = vec![0xff, 0x00];
+    let broken_cid = cid_for(&broken);
+
+    let bytes = frame(
+        "#commit",
+        &Commit {
+            seq: 200,
+            repo: DID,
+            rev: "3laaaaaaaaa24",
+            since: None,
+            blocks: ByteBuf::from(car(&[
+                commit_block("3laaaaaaaaa24"),
+                (broken_cid, broken),
+                (event_cid, event),
+            ])),
+            ops: vec![
+                Op {
+                    action: "create",
+                    path: "community.lexicon.calendar.event/3laaaaaaaaa31",
+                    cid: Some(broken_cid),
+                },
+                Op {
+                    action: "create",
+                    path: "community.lexicon.calendar.event/3laaaaaaaaa32",
+                    cid: Some(event_cid),
+                },
+            ],
+            blobs: vec![],
+            // not a timestamp; the event is stamped with the receive time
+            time: "yesterday",
+            too_big: false,
+            rebase: false,
+        },
+    );
+
+    let decoded = decode_frame(&bytes).unwrap().unwrap();
+    assert_eq!(decoded.events.len(), 1);
+    let commit = decoded.events[0].commit.as_ref().unwrap();
+    assert_eq!(commit.rkey, "3laaaaaaaaa32");
+}
@@ -0,0 +1,190 @@
+//! The image proxy against a local stand-in PDS serving `getBlob`.
+
+use aktivi::{
+    blob::PdsResolver,
+    image_proxy::{ImageProxy, Preset},
+};
+use axum::{extract::Query, routing::get, Router};
+use image::{ImageFormat, RgbImage};
+use ipld_core::cid::Cid;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+const DID: &str = "did:plc:imageproxytest";
+
+fn png(width: u32, height: u32, shade: u8) -> Vec<u8> {
+    let image = RgbImage::from_fn(width, height, |x, _| {
+        image::Rgb([shade, (x % 256) as u8, 0])
+    });
+    let mut out = Cursor::new(Vec::new());
+    image.write_to(&mut out, ImageFormat::Png).unwrap();
+    out.into_inner()
+}
+
+fn blob_cid(data: &[u8]) -> Cid {
+    let hash = Sha256::digest(data);
+    // raw codec, as blobs are
+    Cid::new_v1(0x55, multihash::Multihash::wrap(0x12, &hash).unwrap())
+}
+
+/// A PDS that serves `blobs` by cid and counts requests
+async fn stand_in_pds(blobs: HashMap<String, Vec<u8>>) -> (String, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let blobs = Arc::new(blobs);
+    let app = Router::new().route(
+        "/xrpc/com.atproto.sync.getBlob",
+        get(move |Query(params): Query<HashMap<String, String>>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let blob = blobs.get(&params["cid"]).cloned();
+            async move { blob.ok_or(axum::http::StatusCode::NOT_FOUND) }
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{}", addr), hits)
+}
+
+async fn proxy(
+    blobs: HashMap<String, Vec<u8>>,
+    max_bytes: u64,
+) -> (ImageProxy, PathBuf, Arc<AtomicUsize>) {
+    let (pds_url, hits) = stand_in_pds(blobs).await;
+    let pds = PdsResolver::default();
+    pds.remember(DID, &pds_url).await;
+
+    let dir = std::env::temp_dir().join(format!("aktivi-image-test-{}", rand::random::<u64>()));
+    let proxy = ImageProxy::new(dir.clone(), max_bytes, pds).await.unwrap();
+    (proxy, dir, hits)
+}
+
+fn cached_bytes(dir: &Path) -> u64 {
+    let mut total = 0;
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                dirs.push(entry.path());
+            } else {
+                total += entry.metadata().unwrap().len();
+            }
+        }
+    }
+    total
+}
+
+#[tokio::test]
+async fn resizes_and_serves_from_cache() {
+    let blob = png(1200, 600, 10);
+    let cid = blob_cid(&blob).to_string();
+    let (proxy, dir, hits) = proxy(HashMap::from([(cid.clone(), blob)]), 1 << 30).await;
+
+    let card = proxy.get(DID, &cid, Preset::Card).await.unwrap();
+    let decoded = image::load_from_memory(&card).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (800, 400));
+    assert_eq!(image::guess_format(&card).unwrap(), ImageFormat::Jpeg);
+
+    // smaller than the preset, so left at its size
+    let full = proxy.get(DID, &cid, Preset::Full).await.unwrap();
+    assert_eq!(image::load_from_memory(&full).unwrap().width(), 1200);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    assert_eq!(proxy.get(DID, &cid, Preset::Card).await.unwrap(), card);
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+    // a restarted proxy picks up what's on disk
+    let restarted = ImageProxy::new(dir.clone(), 1 << 30, PdsResolver::default())
+        .await
+        .unwrap();
+    assert_eq!(restarted.get(DID, &cid, Preset::Card).await.unwrap(), card);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn rejects_blob_not_matching_cid() {
+    let cid = blob_cid(&png(100, 100, 1)).to_string();
+    // the PDS hands back different bytes for that cid
+    let (proxy, dir, _) = proxy(HashMap::from([(cid.clone(), png(100, 100, 2))]), 1 << 30).await;
+
+    let err = proxy.get(DID, &cid, Preset::Thumbnail).await.unwrap_err();
+    assert!(format!("{:#}", err).contains("do not match"), "{:#}", err);
+    assert_eq!(cached_bytes(&dir), 0);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn evicts_variants_over_budget() {
+    let blobs: HashMap<String, Vec<u8>> = (0..8)
+        .map(|shade| {
+            let blob = png(1000, 1000, shade * 30);
+            (blob_cid(&blob).to_string(), blob)
+        })
+        .collect();
+    let cids: Vec<String> = blobs.keys().cloned().collect();
+
+    // room for a few thumbnails, not all of them
+    let budget = 12 * 1024;
+    let (proxy, dir, _) = proxy(blobs, budget).await;
+    for cid in &cids {
+        proxy.get(DID, cid, Preset::Thumbnail).await.unwrap();
+    }
+    proxy.sync().await;
+
+    assert!(
+        cached_bytes(&dir) <= budget,
+        "{} bytes cached",
+        cached_bytes(&dir)
+    );
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn renders_concurrent_requests_once() {
+    let blob = png(1200, 600, 20);
+    let cid = blob_cid(&blob).to_string();
+    let (proxy, dir, hits) = proxy(HashMap::from([(cid.clone(), blob)]), 1 << 30).await;
+
+    let proxy = Arc::new(proxy);
+    let requests = (0..8).map(|_| {
+        let proxy = proxy.clone();
+        let cid = cid.clone();
+        tokio::spawn(async move { proxy.get(DID, &cid, Preset::Card).await.unwrap() })
+    });
+    let images: Vec<Vec<u8>> = futures::future::join_all(requests)
+        .await
+        .into_iter()
+        .map(|image| image.unwrap())
+        .collect();
+
+    assert!(images.iter().all(|image| *image == images[0]));
+    assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn rejects_images_over_decode_limits() {
+    // tiny as a png, but wider than we'll decode
+    let blob = png(8001, 1, 30);
+    let cid = blob_cid(&blob).to_string();
+    let (proxy, dir, _) = proxy(HashMap::from([(cid.clone(), blob)]), 1 << 30).await;
+
+    assert!(proxy.get(DID, &cid, Preset::Thumbnail).await.is_err());
+    assert_eq!(cached_bytes(&dir), 0);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
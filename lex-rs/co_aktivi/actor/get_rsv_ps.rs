@@ -216,6 +216,15 @@ pub struct RsvpView<'a> {
     pub indexed_at: jacquard_common::types::string::Datetime,
     #[serde(borrow)]
     pub record: jacquard_common::types::value::Data<'a>,
+    /// Event fields that changed since this RSVP was made
+    #[serde(skip_serializing_if = "std::option::Option::is_none")]
+    #[serde(borrow)]
+    pub subject_changes: std::option::Option<
+        Vec<crate::co_aktivi::event::FieldChange<'a>>,
+    >,
+    /// True when the event has been edited since this RSVP was made
+    #[serde(skip_serializing_if = "std::option::Option::is_none")]
+    pub subject_stale: std::option::Option<bool>,
     #[serde(borrow)]
     pub uri: jacquard_common::types::string::AtUri<'a>,
 }
@@ -341,6 +350,8 @@ pub struct RsvpViewBuilder<'a, S: rsvp_view_state::State> {
         ::core::option::Option<crate::co_aktivi::event::EventViewBasic<'a>>,
         ::core::option::Option<jacquard_common::types::string::Datetime>,
         ::core::option::Option<jacquard_common::types::value::Data<'a>>,
+        ::core::option::Option<Vec<crate::co_aktivi::event::FieldChange<'a>>>,
+        ::core::option::Option<bool>,
         ::core::option::Option<jacquard_common::types::string::AtUri<'a>>,
     ),
     _phantom: ::core::marker::PhantomData<&'a ()>,
@@ -358,7 +369,7 @@ impl<'a> RsvpViewBuilder<'a, rsvp_view_state::Empty> {
     pub fn new() -> Self {
         RsvpViewBuilder {
             _phantom_state: ::core::marker::PhantomData,
-            __unsafe_private_named: (None, None, None, None, None, None),
+            __unsafe_private_named: (None, None, None, None, None, None, None, None),
             _phantom: ::core::marker::PhantomData,
         }
     }
@@ -459,6 +470,38 @@ where
     }
 }
 
+impl<'a, S: rsvp_view_state::State> RsvpViewBuilder<'a, S> {
+    /// Set the `subjectChanges` field (optional)
+    pub fn subject_changes(
+        mut self,
+        value: impl Into<Option<Vec<crate::co_aktivi::event::FieldChange<'a>>>>,
+    ) -> Self {
+        self.__unsafe_private_named.5 = value.into();
+        self
+    }
+    /// Set the `subjectChanges` field to an Option value (optional)
+    pub fn maybe_subject_changes(
+        mut self,
+        value: Option<Vec<crate::co_aktivi::event::FieldChange<'a>>>,
+    ) -> Self {
+        self.__unsafe_private_named.5 = value;
+        self
+    }
+}
+
+impl<'a, S: rsvp_view_state::State> RsvpViewBuilder<'a, S> {
+    /// Set the `subjectStale` field (optional)
+    pub fn subject_stale(mut self, value: impl Into<Option<bool>>) -> Self {
+        self.__unsafe_private_named.6 = value.into();
+        self
+    }
+    /// Set the `subjectStale` field to an Option value (optional)
+    pub fn maybe_subject_stale(mut self, value: Option<bool>) -> Self {
+        self.__unsafe_private_named.6 = value;
+        self
+    }
+}
+
 impl<'a, S> RsvpViewBuilder<'a, S>
 where
     S: rsvp_view_state::State,
@@ -469,7 +512,7 @@ where
         mut self,
         value: impl Into<jacquard_common::types::string::AtUri<'a>>,
     ) -> RsvpViewBuilder<'a, rsvp_view_state::SetUri<S>> {
-        self.__unsafe_private_named.5 = ::core::option::Option::Some(value.into());
+        self.__unsafe_private_named.7 = ::core::option::Option::Some(value.into());
         RsvpViewBuilder {
             _phantom_state: ::core::marker::PhantomData,
             __unsafe_private_named: self.__unsafe_private_named,
@@ -496,7 +539,9 @@ where
             event: self.__unsafe_private_named.2.unwrap(),
             indexed_at: self.__unsafe_private_named.3.unwrap(),
             record: self.__unsafe_private_named.4.unwrap(),
-            uri: self.__unsafe_private_named.5.unwrap(),
+            subject_changes: self.__unsafe_private_named.5,
+            subject_stale: self.__unsafe_private_named.6,
+            uri: self.__unsafe_private_named.7.unwrap(),
             extra_data: Default::default(),
         }
     }
@@ -514,7 +559,9 @@ where
             event: self.__unsafe_private_named.2.unwrap(),
             indexed_at: self.__unsafe_private_named.3.unwrap(),
             record: self.__unsafe_private_named.4.unwrap(),
-            uri: self.__unsafe_private_named.5.unwrap(),
+            subject_changes: self.__unsafe_private_named.5,
+            subject_stale: self.__unsafe_private_named.6,
+            uri: self.__unsafe_private_named.7.unwrap(),
             extra_data: Some(extra_data),
         }
     }
@@ -680,6 +727,40 @@ fn lexicon_doc_co_aktivi_actor_getRSVPs() -> ::jacquard_lexicon::lexicon::Lexico
                                 description: None,
                             }),
                         );
+                        map.insert(
+                            ::jacquard_common::smol_str::SmolStr::new_static(
+                                "subjectChanges",
+                            ),
+                            ::jacquard_lexicon::lexicon::LexObjectProperty::Array(::jacquard_lexicon::lexicon::LexArray {
+                                description: Some(
+                                    ::jacquard_common::CowStr::new_static(
+                                        "Event fields that changed since this RSVP was made",
+                                    ),
+                                ),
+                                items: ::jacquard_lexicon::lexicon::LexArrayItem::Ref(::jacquard_lexicon::lexicon::LexRef {
+                                    description: None,
+                                    r#ref: ::jacquard_common::CowStr::new_static(
+                                        "co.aktivi.event.defs#fieldChange",
+                                    ),
+                                }),
+                                min_length: None,
+                                max_length: None,
+                            }),
+                        );
+                        map.insert(
+                            ::jacquard_common::smol_str::SmolStr::new_static(
+                                "subjectStale",
+                            ),
+                            ::jacquard_lexicon::lexicon::LexObjectProperty::Boolean(::jacquard_lexicon::lexicon::LexBoolean {
+                                description: Some(
+                                    ::jacquard_common::CowStr::new_static(
+                                        "True when the event has been edited since this RSVP was made",
+                                    ),
+                                ),
+                                default: None,
+                                r#const: None,
+                            }),
+                        );
                         map.insert(
                             ::jacquard_common::smol_str::SmolStr::new_static("uri"),
                             ::jacquard_lexicon::lexicon::LexObjectProperty::String(::jacquard_lexicon::lexicon::LexString {